@@ -0,0 +1,300 @@
+//! # Hanging on by a Thread: cli.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the command line interface of the simulation. Every setting is a named option, such as
+//! `--duration 60 --log-file run.txt`, and `--help` and `--version` describe the program.
+//!
+//! The original form of `<seconds_to_run> <T|F>` is still accepted, so
+//! `cargo run 60 T` runs for a minute while logging to `log.txt`, just like
+//! `cargo run -- --duration 60 --log-file log.txt`.
+//!
+//! ## Dependencies
+//! - `clap` for parsing the command line and explaining what was wrong with it.
+//! - `std::path::PathBuf` for the paths given on the command line.
+//! - `project4::event` and `project4::logger` for parsing the levels, formats, and sinks of the log.
+//! - `project4::supervisor::RestartPolicy` for parsing when actors that panic are restarted.
+//! - `project4::steward::StewardMode` for parsing how the stewards pace their deliveries.
+//! - `project4::supply::SupplyKind` for parsing how the stewards pick which stronghold to supply.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::path::PathBuf;
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
+
+use project4::event::{ActorKind, Level};
+use project4::logger::{LogFilter, LogFormat, SinkSpec};
+use project4::steward::StewardMode;
+use project4::supervisor::RestartPolicy;
+use project4::supply::SupplyKind;
+
+/// Constant that contains the name of the file to log to when logging to a file, unless another
+/// is given
+pub const LOG_FILE:&str = "log.txt";
+
+/// Constant that contains the name of the file that describes the world, unless another is given
+pub const WORLD_FILE:&str = "world.toml";
+
+/// Where the original form of the command line sends the log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogMode {
+    /// Log to the log file.
+    #[value(name = "T")]
+    File,
+    /// Log to the console.
+    #[value(name = "F")]
+    Console
+}
+
+/// A simulation of dragon riders delivering resources from a steward to the strongholds of Pern.
+///
+/// Every setting is a named option. The original form, `<SECONDS> <T|F>`, is still accepted.
+#[derive(Debug, Parser)]
+#[command(version, about, allow_negative_numbers = true)]
+pub struct Cli {
+    /// How many seconds to run for, in the original form. Use --duration instead
+    #[arg(value_name = "SECONDS")]
+    seconds: Option<i64>,
+
+    /// Whether to log to log.txt (T) or to the console (F), in the original form. Use --log-file
+    /// instead
+    #[arg(value_name = "T|F")]
+    log_mode: Option<LogMode>,
+
+    /// How many seconds to run for. 0 or less runs until Ctrl-C is pressed
+    #[arg(short, long, value_name = "SECONDS", conflicts_with = "seconds")]
+    duration: Option<i64>,
+
+    /// The scenario file describing the world to simulate [default: world.toml, if it exists]
+    #[arg(short, long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// How many dragon riders carry each resource, overriding the scenario file [default: 1]
+    #[arg(short, long, value_name = "COUNT", value_parser = clap::value_parser!(u32).range(1..))]
+    pub riders: Option<u32>,
+
+    /// Seeds every random number generator, so a run can be reproduced
+    #[arg(short, long, value_name = "NUMBER")]
+    pub seed: Option<u64>,
+
+    /// Run on a simulated clock that jumps straight to the next wake up
+    #[arg(long)]
+    pub virtual_time: bool,
+
+    /// Also write the statistics of the run to PATH as JSON
+    #[arg(long, value_name = "PATH")]
+    pub stats_json: Option<PathBuf>,
+
+    /// Log to PATH instead of the console
+    #[arg(short, long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Add to the end of an existing log file instead of replacing it
+    #[arg(long)]
+    pub append: bool,
+
+    /// Rotate a log file once it would grow past BYTES
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub log_max_bytes: Option<u64>,
+
+    /// The format events are logged in
+    #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_format)]
+    pub log_format: LogFormat,
+
+    /// Leave out events less important than LEVEL: trace, debug, info or warn
+    #[arg(long, value_name = "LEVEL", default_value = "trace", value_parser = parse_level)]
+    log_level: Level,
+
+    /// Override --log-level for one kind of actor: simulation, steward, rider or stronghold
+    #[arg(long, value_name = "ACTOR=LEVEL", value_parser = parse_actor_level)]
+    actor_level: Vec<(ActorKind, Level)>,
+
+    /// Also log to another sink, such as console,level=info or file=run.jsonl,format=jsonl
+    #[arg(long, value_name = "SPEC", value_parser = parse_sink)]
+    pub sink: Vec<SinkSpec>,
+
+    /// Never colour the console, even when it is a terminal
+    #[arg(long)]
+    pub no_colour: bool,
+
+    /// Warn about every actor and the signal it waits on once none has made progress for SECONDS
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub watchdog: Option<u64>,
+
+    /// Stop the run and exit with an error once the watchdog finds it has stalled
+    #[arg(long, requires = "watchdog")]
+    pub watchdog_abort: bool,
+
+    /// Log Jain's fairness index and the most starved stronghold every SECONDS while running
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub fairness_every: Option<u64>,
+
+    /// Restart an actor whose thread panics: never, always, or at most this many times
+    #[arg(long, value_name = "POLICY", default_value_t = RestartPolicy::Never)]
+    pub restart: RestartPolicy,
+
+    /// Pace the stewards: lockstep, the default for a single steward, or pipelined
+    #[arg(long, value_name = "MODE")]
+    pub steward_mode: Option<StewardMode>,
+
+    /// Pick the stronghold to supply next: uniform, weighted, round-robin or demand
+    #[arg(long, value_name = "POLICY")]
+    pub supply: Option<SupplyKind>,
+
+    /// Run for the duration in both steward modes with the same seed and compare their throughput
+    #[arg(long, conflicts_with = "steward_mode")]
+    pub compare_modes: bool
+}
+
+impl Cli {
+    /// Parses the command line, printing what was wrong with it and exiting if it is invalid.
+    ///
+    /// # Returns
+    /// The settings given on the command line.
+    pub fn parse_args() -> Cli {
+        Cli::parse().check().unwrap_or_else(|error| error.exit())
+    }
+
+    /// Checks the settings that clap can't check on its own.
+    ///
+    /// # Returns
+    /// An `Err` explaining what is wrong if the settings can't be used together.
+    fn check(self) -> Result<Cli, clap::Error> {
+        if self.seconds.is_some() && self.log_mode.is_none() {
+            return Err(Cli::command().error(ErrorKind::MissingRequiredArgument,
+                "the original form needs both <SECONDS> and <T|F>, such as `60 T`"));
+        }
+        if self.seconds.is_none() && self.duration.is_none() {
+            return Err(Cli::command().error(ErrorKind::MissingRequiredArgument,
+                "how long to run for must be given with --duration <SECONDS>"));
+        }
+        if self.config.as_ref().is_some_and(|path| !path.exists()) {
+            return Err(Cli::command().error(ErrorKind::InvalidValue,
+                "the scenario file given with --config does not exist"));
+        }
+        if self.log_mode == Some(LogMode::Console) && self.log_file.is_some() {
+            return Err(Cli::command().error(ErrorKind::ArgumentConflict,
+                "F logs to the console, so it can't be used with --log-file"));
+        }
+        Ok(self)
+    }
+
+    /// Returns how many seconds to run for.
+    pub fn duration(&self) -> i64 {
+        self.duration.or(self.seconds).unwrap_or_default()
+    }
+
+    /// Returns the scenario file, which may not exist if none was given.
+    pub fn world_file(&self) -> PathBuf {
+        self.config.clone().unwrap_or_else(|| PathBuf::from(WORLD_FILE))
+    }
+
+    /// Returns the file to log to, or `None` to log to the console.
+    pub fn log_file(&self) -> Option<PathBuf> {
+        match (self.log_mode, &self.log_file) {
+            (_, Some(path)) => Some(path.clone()),
+            (Some(LogMode::File), None) => Some(PathBuf::from(LOG_FILE)),
+            _ => None
+        }
+    }
+
+    /// Returns which events are logged to the console or log file.
+    pub fn log_filter(&self) -> LogFilter {
+        let mut filter = LogFilter::new(self.log_level);
+        for (actor, level) in &self.actor_level {
+            filter.set_actor_level(*actor, *level);
+        }
+        filter
+    }
+}
+
+/// Parses a log format.
+///
+/// # Arguments
+/// * `value`: Either text or jsonl.
+fn parse_format(value:&str) -> Result<LogFormat, String> {
+    value.parse()
+}
+
+/// Parses a level.
+///
+/// # Arguments
+/// * `value`: One of trace, debug, info or warn.
+fn parse_level(value:&str) -> Result<Level, String> {
+    value.parse()
+}
+
+/// Parses the level of a kind of actor.
+///
+/// # Arguments
+/// * `value`: The kind of actor and its level, such as rider=trace.
+fn parse_actor_level(value:&str) -> Result<(ActorKind, Level), String> {
+    let Some((actor, level)) = value.split_once('=') else {
+        return Err("expected <actor>=<level>, such as rider=trace".to_string());
+    };
+    Ok((actor.parse()?, level.parse()?))
+}
+
+/// Parses a sink.
+///
+/// # Arguments
+/// * `value`: The target of the sink followed by its settings, such as console,level=info.
+fn parse_sink(value:&str) -> Result<SinkSpec, String> {
+    value.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use clap::{error::ErrorKind, Parser};
+
+    use super::{Cli, LOG_FILE};
+
+    /// Parses and checks a command line the way `parse_args` does, without exiting.
+    ///
+    /// # Arguments
+    /// * `args`: The arguments given after the name of the program.
+    fn parse(args:&[&str]) -> Result<Cli, ErrorKind> {
+        Cli::try_parse_from(["project4"].iter().chain(args))
+            .and_then(Cli::check)
+            .map_err(|error| error.kind())
+    }
+
+    #[test]
+    fn the_original_form_sets_the_duration_and_where_to_log() {
+        let cli = parse(&["60", "T"]).unwrap();
+        assert_eq!(cli.duration(), 60);
+        assert_eq!(cli.log_file(), Some(PathBuf::from(LOG_FILE)));
+
+        let cli = parse(&["-5", "F"]).unwrap();
+        assert_eq!(cli.duration(), -5);
+        assert_eq!(cli.log_file(), None);
+
+        let cli = parse(&["30", "T", "--log-file", "run.txt"]).unwrap();
+        assert_eq!(cli.log_file(), Some(PathBuf::from("run.txt")));
+    }
+
+    #[test]
+    fn the_original_form_rejects_what_it_cannot_use() {
+        assert_eq!(parse(&["60"]).err(), Some(ErrorKind::MissingRequiredArgument));
+        assert_eq!(parse(&["60", "X"]).err(), Some(ErrorKind::InvalidValue));
+        assert_eq!(parse(&["sixty", "T"]).err(), Some(ErrorKind::ValueValidation));
+        assert_eq!(parse(&["60", "F", "--log-file", "run.txt"]).err(),
+                   Some(ErrorKind::ArgumentConflict));
+        assert_eq!(parse(&["60", "T", "--duration", "30"]).err(),
+                   Some(ErrorKind::ArgumentConflict));
+    }
+
+    #[test]
+    fn named_options_work_without_the_original_form() {
+        let cli = parse(&["--duration", "45"]).unwrap();
+        assert_eq!(cli.duration(), 45);
+        assert_eq!(cli.log_file(), None);
+        assert_eq!(parse(&[]).err(), Some(ErrorKind::MissingRequiredArgument));
+    }
+}
@@ -0,0 +1,220 @@
+//! # Hanging on by a Thread: clock.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the clocks that the actors use to tell and pass time:
+//! - `RealClock` sleeps on the wall clock, so a one-hour run takes an hour
+//! - `VirtualClock` is a discrete-event clock that jumps straight to the next wake up time once
+//!   every actor is either sleeping or waiting on a signal, so days of activity pass in seconds
+//!
+//! Both clocks stop sleeping as soon as the shutdown token is triggered.
+//!
+//! For the virtual clock to know when every actor is idle, actors tell it when they start and
+//! stop running, and each `Signal` tells it when actors wait on it and when it lets them through.
+//!
+//! ## Dependencies
+//! - `std::sync::{Arc, Condvar, Mutex}` for sharing the state of the virtual clock between actors.
+//! - `std::collections::{BinaryHeap, HashMap}` for tracking sleeping and parked actors.
+//! - `std::time::{Duration, Instant}` for measuring time.
+//! - `crate::sync::lock` for locking the state of the virtual clock.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap}};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{shutdown::{Shutdown, POLL_INTERVAL}, sync::lock};
+
+/// A source of time for the actors of the simulation.
+///
+/// Only `now` and `sleep` are needed to tell and pass time. The other methods let a clock track
+/// which actors are running; they do nothing unless a clock overrides them.
+pub trait Clock: Send + Sync {
+    /// Returns how much time has passed since the clock was created.
+    fn now(&self) -> Duration;
+
+    /// Blocks the calling actor until `duration` has passed or the simulation shuts down.
+    ///
+    /// # Arguments
+    /// * `duration`: How long the actor sleeps for.
+    fn sleep(&self, duration:Duration);
+
+    /// Counts an actor as running. Called once for each actor before its thread is spawned.
+    fn register(&self) {}
+
+    /// Stops counting an actor as running. Called when an actor will never run again.
+    fn deregister(&self) {}
+
+    /// Marks an actor as waiting on a signal. Called by the signal with its lock held, once for
+    /// every actor that has to wait because the signal has not been raised.
+    ///
+    /// # Arguments
+    /// * `signal`: The key of the signal being waited on.
+    fn park(&self, _signal:usize) {}
+
+    /// Hands a running slot to an actor waiting on a signal. Called by the signal with its lock
+    /// held, once for every waiting actor that a raise lets through.
+    ///
+    /// # Arguments
+    /// * `signal`: The key of the signal being raised.
+    fn unpark(&self, _signal:usize) {}
+}
+
+/// Structure that represents a clock that follows the wall clock
+///
+/// # Fields
+/// - `start`: The instant the clock was created
+/// - `shutdown`: The token that cuts sleeps short
+pub struct RealClock {
+    start: Instant,
+    shutdown: Arc<Shutdown>
+}
+
+impl RealClock {
+    /// Constructs a new `RealClock` starting now.
+    ///
+    /// # Arguments
+    /// * `shutdown`: The token that cuts sleeps short.
+    pub fn new(shutdown:Arc<Shutdown>) -> RealClock {
+        RealClock { start: Instant::now(), shutdown }
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration:Duration) {
+        self.shutdown.wait_timeout(duration);
+    }
+}
+
+/// The shared state of a virtual clock.
+///
+/// # Fields
+/// - `now`: The simulated time that has passed
+/// - `running`: The amount of actors that are neither sleeping nor waiting on a signal
+/// - `sleepers`: The wake up time of every sleeping actor, earliest first
+/// - `parked`: The amount of actors waiting on each signal, keyed by signal
+#[derive(Default)]
+struct VirtualState {
+    now: Duration,
+    running: usize,
+    sleepers: BinaryHeap<Reverse<Duration>>,
+    parked: HashMap<usize, usize>
+}
+
+/// Structure that represents a discrete-event clock
+///
+/// Simulated time stands still while any actor is running. Once every actor is either sleeping or
+/// waiting on a signal, the clock jumps to the earliest wake up time and wakes those sleepers.
+///
+/// # Fields
+/// - `state`: The simulated time and what each actor is doing
+/// - `advanced`: Notifies sleepers that simulated time has moved forward
+/// - `shutdown`: The token that cuts sleeps short
+pub struct VirtualClock {
+    state: Mutex<VirtualState>,
+    advanced: Condvar,
+    shutdown: Arc<Shutdown>
+}
+
+impl VirtualClock {
+    /// Constructs a new `VirtualClock` starting at zero with no actors.
+    ///
+    /// # Arguments
+    /// * `shutdown`: The token that cuts sleeps short.
+    pub fn new(shutdown:Arc<Shutdown>) -> VirtualClock {
+        VirtualClock {
+            state: Mutex::new(VirtualState::default()),
+            advanced: Condvar::new(),
+            shutdown
+        }
+    }
+
+    /// Moves simulated time forward to the next wake up time if no actor is running.
+    ///
+    /// Simulated time stops once the simulation shuts down, so actors leaving their loops can't
+    /// move it forward.
+    ///
+    /// # Arguments
+    /// * `state`: The locked state of the clock.
+    fn advance(&self, state:&mut VirtualState) {
+        if state.running > 0 || self.shutdown.is_triggered() {
+            return;
+        }
+        if let Some(&Reverse(next)) = state.sleepers.peek() {
+            state.now = state.now.max(next);
+            while let Some(&Reverse(wake)) = state.sleepers.peek() {
+                if wake > state.now {
+                    break;
+                }
+                state.sleepers.pop();
+                state.running += 1;
+            }
+            self.advanced.notify_all();
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        lock(&self.state).now
+    }
+
+    fn sleep(&self, duration:Duration) {
+        if duration.is_zero() {
+            return;
+        }
+        let mut state = lock(&self.state);
+        let wake = state.now + duration;
+        state.sleepers.push(Reverse(wake));
+        // A thread that sleeps without registering first must not wrap the count around
+        state.running = state.running.saturating_sub(1);
+        self.advance(&mut state);
+        while state.now < wake {
+            if self.shutdown.is_triggered() {
+                // Counts the actor as running again, since it stopped sleeping early
+                state.running += 1;
+                return;
+            }
+            state = self.advanced.wait_timeout(state, POLL_INTERVAL)
+                .unwrap_or_else(PoisonError::into_inner).0;
+        }
+    }
+
+    fn register(&self) {
+        lock(&self.state).running += 1;
+    }
+
+    fn deregister(&self) {
+        let mut state = lock(&self.state);
+        // An actor that stopped waiting on a signal because of a shutdown was never unparked
+        state.running = state.running.saturating_sub(1);
+        self.advance(&mut state);
+    }
+
+    fn park(&self, signal:usize) {
+        let mut state = lock(&self.state);
+        *state.parked.entry(signal).or_insert(0) += 1;
+        state.running = state.running.saturating_sub(1);
+        self.advance(&mut state);
+    }
+
+    fn unpark(&self, signal:usize) {
+        let mut state = lock(&self.state);
+        if let Some(parked) = state.parked.get_mut(&signal) {
+            if *parked > 0 {
+                *parked -= 1;
+                state.running += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! # Hanging on by a Thread: context.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the context shared by every actor of the simulation:
+//! - the handle that events are sent to the log thread with
+//! - the clock that time is told and passed with
+//! - the shutdown token that tells the actors to stop
+//! - the statistics that the actors record while they run
+//! - the progress of every actor, which the watchdog checks for stalls
+//! - the order the strongholds started waiting in, which demand driven stewards supply them in
+//!
+//! ## Dependencies
+//! - `std::sync::{Arc, Mutex}` for sharing the context between threads.
+//! - `crate::logthread::LogHandle` for logging without waiting on file I/O.
+//! - `crate::supply::Demand` for the queue of waiting strongholds.
+//! - `crate::sync::lock` for recording statistics.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::sync::{Arc, Mutex};
+
+use crate::{clock::Clock, event::{Event, EventKind}, logthread::LogHandle};
+use crate::{shutdown::Shutdown, stats::Statistics, supply::Demand, sync::lock};
+use crate::watchdog::Progress;
+#[cfg(test)]
+use crate::{clock::RealClock, logthread::Captured};
+
+/// Structure that represents what every actor shares
+///
+/// # Fields
+/// - `logger`: The handle that events are sent to the log thread with
+/// - `clock`: The clock used to tell and pass time
+/// - `shutdown`: The token that tells the actors to stop
+/// - `stats`: The statistics recorded by the actors
+/// - `progress`: When each actor last made progress
+/// - `demand`: The order the strongholds started waiting for their resources in
+#[derive(Clone)]
+pub struct Context {
+    pub logger: LogHandle,
+    pub clock: Arc<dyn Clock>,
+    pub shutdown: Arc<Shutdown>,
+    pub stats: Arc<Mutex<Statistics>>,
+    pub progress: Arc<Progress>,
+    pub demand: Arc<Demand>
+}
+
+impl Context {
+    /// Constructs a new `Context` with no statistics, progress, or demand recorded.
+    ///
+    /// # Arguments
+    /// * `logger`: The handle that events are sent to the log thread with.
+    /// * `clock`: The clock used to tell and pass time.
+    /// * `shutdown`: The token that tells the actors to stop.
+    pub fn new(logger:LogHandle, clock:Arc<dyn Clock>, shutdown:Arc<Shutdown>) -> Context {
+        Context {
+            logger,
+            progress: Arc::new(Progress::new(Arc::clone(&clock))),
+            clock,
+            shutdown,
+            stats: Arc::new(Mutex::new(Statistics::new())),
+            demand: Arc::new(Demand::new())
+        }
+    }
+
+    /// Sends an event to the log thread, stamped with the current time and thread. Never waits for
+    /// the event to be written.
+    ///
+    /// # Arguments
+    /// * `kind`: What happened.
+    pub fn log(&self, kind:EventKind) {
+        self.logger.log(Event::new(self.clock.now(), kind));
+    }
+
+    /// Records statistics about what an actor did.
+    ///
+    /// # Arguments
+    /// * `record`: Updates the statistics.
+    pub fn record<F>(&self, record:F) where F: FnOnce(&mut Statistics) {
+        record(&mut lock(&self.stats));
+    }
+}
+
+#[cfg(test)]
+impl Context {
+    /// Constructs a new `Context` on a real clock whose events are kept for a test to read.
+    ///
+    /// # Returns
+    /// The context, and the events that are logged with it.
+    pub(crate) fn capture() -> (Context, Captured) {
+        let shutdown = Arc::new(Shutdown::new());
+        let clock = Arc::new(RealClock::new(Arc::clone(&shutdown)));
+        let (logger, captured) = LogHandle::capture();
+        (Context::new(logger, clock, shutdown), captured)
+    }
+}
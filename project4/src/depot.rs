@@ -2,12 +2,21 @@
 //! # Version: May 3rd 2024
 //!
 //! ## Description
-//! Sets up the depot for holding each type of resource:
-//! - klah
-//! - burnstone
-//! - seaplum
-//! Has the functionality of taking or placing each resource, checking if it is empty,
-//! and testing the status of each resource.
+//! Sets up the depot for holding a stock of each type of resource in the resource catalog.
+//!
+//! Has the functionality of placing or taking a unit of each resource. Each resource can hold at
+//! most the number of units the scenario gives it; placing into a full stockpile or taking from an
+//! empty one is reported back to the caller instead of being silently ignored.
+//!
+//! Every unit is tagged with the delivery it is part of and the stronghold that delivery was
+//! collected for, and the units of each resource are taken in the order they were placed, so the
+//! dragon riders' depot can hand each delivery to its own stronghold.
+//!
+//! A unit that a dragon rider takes still counts against the capacity until the stronghold it was
+//! delivered to receives it, so the capacity bounds every unit on its way to a stronghold. A unit
+//! that a dragon rider took but could not carry, because it panicked, is put back instead. When a
+//! stronghold releases its units, the depot tells it how many stewards are waiting for room, so
+//! several stewards can feed the depot at once without overfilling it.
 //!
 //! ## Authors
 //! - Dylan Miller
@@ -16,73 +25,330 @@
 //! ## Instructor
 //! - Dr. William Kreahling
 
-use crate::steward::RESOURCES;
+use std::{collections::{HashMap, VecDeque}, fmt, mem};
+
+use crate::resource::Resource;
+
+/// Error returned when the depot cannot accept a resource.
+///
+/// # Variants
+/// - `Full`: The stockpile of `resource` already holds `capacity` units.
+/// - `Unknown`: The resource is not part of the catalog the depot was built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DepotError {
+    Full { resource: String, capacity: u32 },
+    Unknown(String)
+}
+
+impl fmt::Display for DepotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DepotError::Full { resource, capacity } => {
+                write!(f, "the depot is full of {} ({} units)", resource, capacity)
+            },
+            DepotError::Unknown(resource) => write!(f, "the depot does not stock {}", resource)
+        }
+    }
+}
+
+impl std::error::Error for DepotError {}
+
+/// Structure that represents a unit of a resource on its way to a stronghold
+///
+/// # Fields
+/// - `resource`: The resource the unit is of
+/// - `delivery`: The number of the delivery the unit is part of, which no other delivery to the
+///   same depot shares
+/// - `stronghold`: The name of the stronghold the delivery was collected for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unit {
+    pub resource: Resource,
+    pub delivery: u64,
+    pub stronghold: String
+}
 
 /// Structure that represents a Depot that houses resources
 ///
 /// # Fields
-/// - `burnstone`: provides defense and power to the strongholds
-/// - `seaplum`:  a delicious and nutritious foodstuff
-/// - `klah`:  a nutritious and tasty drink
+/// - `stock`: The units of each resource in the depot, in the order they were placed
+/// - `held`: The units of each resource that were taken but not yet received by a stronghold
+/// - `capacities`: The maximum amount of units that can be stored of each resource
+/// - `waiting`: The amount of stewards waiting for a stronghold to release its units
+/// - `deliveries`: How many deliveries have been started, which numbers the next one
 pub struct Depot {
-    burnstone: String,
-    seaplum: String,
-    klah: String
+    stock: HashMap<Resource, VecDeque<Unit>>,
+    held: HashMap<Resource, u32>,
+    capacities: HashMap<Resource, u32>,
+    waiting: u32,
+    deliveries: u64
 }
 
 impl Depot {
-    /// Creates a new, empty `Depot`.
-    pub fn new() -> Depot {
+    /// Creates a new, empty `Depot` with a stockpile for every resource it is given a capacity for.
+    ///
+    /// # Arguments
+    /// * `capacities`: The maximum amount of units that can be stored of each resource.
+    pub fn new(capacities:HashMap<Resource, u32>) -> Depot {
         Depot {
-            burnstone: String::new(),
-            seaplum: String::new(),
-            klah: String::new()
+            stock: capacities.keys().map(|resource| (resource.clone(), VecDeque::new())).collect(),
+            held: capacities.keys().map(|resource| (resource.clone(), 0)).collect(),
+            capacities,
+            waiting: 0,
+            deliveries: 0
         }
     }
 
-    /// Places burnstone within the depot.
-    pub fn place_burnstone(&mut self) {
-        self.burnstone = String::from(RESOURCES[0]);
+    /// Starts a new delivery, which every unit placed as part of it is tagged with.
+    ///
+    /// # Returns
+    /// The number of the delivery.
+    pub fn start_delivery(&mut self) -> u64 {
+        self.deliveries += 1;
+        self.deliveries
+    }
+
+    /// Places a unit of a resource within the depot.
+    ///
+    /// # Arguments
+    /// * `unit`: The unit being placed.
+    ///
+    /// # Returns
+    /// An `Err` if the depot is already full of the resource or does not stock it.
+    pub fn place(&mut self, unit:Unit) -> Result<(), DepotError> {
+        let resource = &unit.resource;
+        let stock = self.stock.get_mut(resource)
+            .ok_or_else(|| DepotError::Unknown(resource.name().to_string()))?;
+        let capacity = self.capacities[resource];
+        if stock.len() as u32 + self.held[resource] >= capacity {
+            return Err(DepotError::Full { resource: resource.name().to_string(), capacity });
+        }
+        stock.push_back(unit);
+        Ok(())
     }
 
-    /// Retrieves all burnstone from the depot, leaving it empty.
+    /// Retrieves the unit of a resource that has been in the depot the longest.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource being taken.
     ///
     /// # Returns
-    /// A `String` containing the burnstone that was stored.
-    pub fn take_burnstone(&mut self) -> String {
-        let burnstone = self.burnstone.clone();
-        self.burnstone = String::new();
-        burnstone
+    /// The unit that was stored, or `None` if there was none.
+    pub fn take(&mut self, resource:&Resource) -> Option<Unit> {
+        let unit = self.stock.get_mut(resource)?.pop_front()?;
+        *self.held.entry(resource.clone()).or_insert(0) += 1;
+        Some(unit)
     }
 
-    /// Places seaplum within the depot.
-    pub fn place_seaplum(&mut self) {
-        self.seaplum = String::from(RESOURCES[1]);
+    /// Puts a unit that was taken back at the front of its stockpile, so it is the next one taken,
+    /// when it can no longer reach a stronghold.
+    ///
+    /// # Arguments
+    /// * `unit`: The unit being put back.
+    pub fn put_back(&mut self, unit:Unit) {
+        if let Some(held) = self.held.get_mut(&unit.resource) {
+            *held = held.saturating_sub(1);
+        }
+        if let Some(stock) = self.stock.get_mut(&unit.resource) {
+            stock.push_front(unit);
+        }
+    }
+
+    /// Test to see if every unit of a set of resources can be placed in the depot at once.
+    ///
+    /// # Arguments
+    /// * `resources`: The units that would be placed.
+    pub fn has_room(&self, resources:&[Resource]) -> bool {
+        self.room_for(resources).is_ok()
+    }
+
+    /// Checks that every unit of a set of resources can be placed in the depot at once.
+    ///
+    /// # Arguments
+    /// * `resources`: The units that would be placed.
+    ///
+    /// # Returns
+    /// An `Err` if the depot has no room left for that many units of a resource, or does not stock
+    /// it.
+    pub fn room_for(&self, resources:&[Resource]) -> Result<(), DepotError> {
+        for (resource, count) in Depot::tally(resources) {
+            let capacity = *self.capacities.get(resource)
+                .ok_or_else(|| DepotError::Unknown(resource.name().to_string()))?;
+            if self.stock(resource) + self.held(resource) + count > capacity {
+                return Err(DepotError::Full { resource: resource.name().to_string(), capacity });
+            }
+        }
+        Ok(())
     }
 
-    /// Retrieves all seaplum from the depot, leaving it empty.
+    /// Checks that every unit of a set of resources could be placed in the depot at once, were
+    /// it empty.
+    ///
+    /// # Arguments
+    /// * `resources`: The units that would be placed.
     ///
     /// # Returns
-    /// A `String` containing the seaplum that was stored.
-    pub fn take_seaplum(&mut self) -> String {
-        let seaplum = self.seaplum.clone();
-        self.seaplum = String::new();
-        seaplum
+    /// An `Err` if the depot can never hold that many units of a resource, or does not stock it.
+    pub fn fits(&self, resources:&[Resource]) -> Result<(), DepotError> {
+        for (resource, count) in Depot::tally(resources) {
+            let capacity = *self.capacities.get(resource)
+                .ok_or_else(|| DepotError::Unknown(resource.name().to_string()))?;
+            if count > capacity {
+                return Err(DepotError::Full { resource: resource.name().to_string(), capacity });
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts the units of each resource in a set of resources.
+    ///
+    /// # Arguments
+    /// * `resources`: The units that are counted.
+    fn tally(resources:&[Resource]) -> HashMap<&Resource, u32> {
+        let mut tally = HashMap::new();
+        for resource in resources {
+            *tally.entry(resource).or_insert(0) += 1;
+        }
+        tally
     }
 
-    /// Places klah within the depot.
-    pub fn place_klah(&mut self) {
-        self.klah = String::from(RESOURCES[2]);
+    /// Counts a steward as waiting for a stronghold to release its units.
+    pub fn add_waiter(&mut self) {
+        self.waiting += 1;
     }
 
-    /// Retrieves all klah from the depot, leaving it empty.
+    /// Frees the room taken by units that a stronghold has received.
+    ///
+    /// # Arguments
+    /// * `resources`: The units the stronghold received.
     ///
     /// # Returns
-    /// A `String` containing the klah that was stored.
-    pub fn take_klah(&mut self) -> String {
-        let klah = self.klah.clone();
-        self.klah = String::new();
-        klah
+    /// How many stewards were waiting for room, each of which should now be woken.
+    pub fn release(&mut self, resources:&[Resource]) -> u32 {
+        for resource in resources {
+            if let Some(held) = self.held.get_mut(resource) {
+                *held = held.saturating_sub(1);
+            }
+        }
+        mem::take(&mut self.waiting)
+    }
+
+    /// Returns how many units of a resource are in the depot.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource being counted.
+    pub fn stock(&self, resource:&Resource) -> u32 {
+        self.stock.get(resource).map_or(0, |units| units.len() as u32)
+    }
+
+    /// Returns how many units of a resource were taken but not yet received by a stronghold.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource being counted.
+    pub fn held(&self, resource:&Resource) -> u32 {
+        self.held.get(resource).copied().unwrap_or(0)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, slice};
+
+    use super::{Depot, DepotError, Unit};
+    use crate::resource::Resource;
+
+    /// Builds a depot that holds two units of Klah and one of Seaplum.
+    fn depot() -> (Depot, Resource, Resource) {
+        let klah = Resource::new("Klah", "a nutritious and tasty drink");
+        let seaplum = Resource::new("Seaplum", "a delicious and nutritious foodstuff");
+        let capacities = HashMap::from([(klah.clone(), 2), (seaplum.clone(), 1)]);
+        (Depot::new(capacities), klah, seaplum)
+    }
+
+    /// Builds a unit of a delivery to the Kitchen.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource the unit is of.
+    /// * `delivery`: The number of the delivery the unit is part of.
+    fn unit(resource:&Resource, delivery:u64) -> Unit {
+        Unit { resource: resource.clone(), delivery, stronghold: "Kitchen".to_string() }
+    }
+
+    #[test]
+    fn a_full_stockpile_refuses_more_units() {
+        let (mut depot, klah, _) = depot();
+        assert!(depot.place(unit(&klah, 1)).is_ok());
+        assert!(depot.place(unit(&klah, 2)).is_ok());
+        assert_eq!(depot.place(unit(&klah, 3)),
+                   Err(DepotError::Full { resource: "Klah".to_string(), capacity: 2 }));
+        assert_eq!(depot.stock(&klah), 2);
+
+        let gold = Resource::new("Gold", "not stocked");
+        assert_eq!(depot.place(unit(&gold, 4)), Err(DepotError::Unknown("Gold".to_string())));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn units_are_taken_in_the_order_they_were_placed() {
+        let (mut depot, klah, seaplum) = depot();
+        assert_eq!(depot.take(&klah), None);
+        depot.place(unit(&klah, 1)).unwrap();
+        depot.place(unit(&klah, 2)).unwrap();
+        assert_eq!(depot.take(&klah), Some(unit(&klah, 1)));
+        assert_eq!(depot.take(&klah), Some(unit(&klah, 2)));
+        assert_eq!(depot.take(&seaplum), None);
+    }
+
+    #[test]
+    fn a_taken_unit_holds_its_room_until_it_is_released() {
+        let (mut depot, klah, seaplum) = depot();
+        depot.place(unit(&klah, 1)).unwrap();
+        depot.place(unit(&klah, 2)).unwrap();
+        depot.take(&klah).unwrap();
+        assert_eq!((depot.stock(&klah), depot.held(&klah)), (1, 1));
+        assert!(!depot.has_room(slice::from_ref(&klah)));
+        assert!(depot.place(unit(&klah, 3)).is_err());
+
+        depot.add_waiter();
+        depot.add_waiter();
+        assert_eq!(depot.release(&[klah.clone(), seaplum.clone()]), 2);
+        assert_eq!(depot.release(slice::from_ref(&klah)), 0);
+        assert_eq!(depot.held(&klah), 0);
+        assert_eq!(depot.held(&seaplum), 0);
+        assert!(depot.has_room(slice::from_ref(&klah)));
+        assert!(depot.place(unit(&klah, 3)).is_ok());
+    }
+
+    #[test]
+    fn a_unit_put_back_is_the_next_one_taken() {
+        let (mut depot, klah, _) = depot();
+        depot.place(unit(&klah, 1)).unwrap();
+        depot.place(unit(&klah, 2)).unwrap();
+        let taken = depot.take(&klah).unwrap();
+        depot.put_back(taken);
+        assert_eq!(depot.held(&klah), 0);
+        assert_eq!(depot.take(&klah), Some(unit(&klah, 1)));
+    }
+
+    #[test]
+    fn a_set_fits_only_if_every_unit_has_room_at_once() {
+        let (mut depot, klah, seaplum) = depot();
+        let set = [klah.clone(), klah.clone(), seaplum.clone()];
+        assert!(depot.has_room(&set));
+        assert!(depot.fits(&set).is_ok());
+        depot.place(unit(&seaplum, 1)).unwrap();
+        assert!(!depot.has_room(&set));
+        assert_eq!(depot.room_for(&set),
+                   Err(DepotError::Full { resource: "Seaplum".to_string(), capacity: 1 }));
+        assert!(depot.fits(&set).is_ok());
+
+        let too_many = [seaplum.clone(), seaplum];
+        assert!(depot.fits(&too_many).is_err());
+    }
+
+    #[test]
+    fn deliveries_are_numbered_in_order() {
+        let (mut depot, _, _) = depot();
+        assert_eq!(depot.start_delivery(), 1);
+        assert_eq!(depot.start_delivery(), 2);
+    }
+}
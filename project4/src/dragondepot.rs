@@ -3,15 +3,19 @@
 //!
 //! ## Description
 //! Sets up the dragon depot structure which holds:
-//! - The collected items
-//! - A count of the items
+//! - The collected items of each delivery, counted by resource
+//! - The recipe of each stronghold
 //! - Signals for each stronghold messaging that the resources are available
-//! This file also checks for each resource. Then it places and depletes each resource.
+//!
+//! Every unit a dragon rider places is tagged with the delivery it is part of and the stronghold
+//! that delivery was collected for. The units of each delivery are collected apart from every
+//! other delivery, and once they satisfy the recipe of their stronghold, that stronghold is
+//! signaled. Several deliveries can be on the way at once without a unit ever completing a
+//! delivery it wasn't collected for, even when the recipe of one stronghold is part of another's.
 //!
 //! ## Dependencies
-//! This module utilizes Rust's standard synchronization primitives from the `std::sync` package,
-//! including `Arc`, `Mutex`, and `Condvar`, to ensure thread-safe operations across multiple
-//! dragon riders and resource distribution handlers.
+//! This module utilizes `Arc` from the `std::sync` package and the `Signal` type to ensure
+//! thread-safe operations across multiple dragon riders and resource distribution handlers.
 //!
 //! ## Authors
 //! - Dylan Miller
@@ -20,118 +24,111 @@
 //! ## Instructor
 //! - Dr. William Kreahling
 
-use std::sync::{Arc,Mutex,Condvar};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{depot::Unit, recipe::Recipe, resource::Resource, signal::Signal};
 
-/// Constant for the maximum amount of items allowed.
-const MAX_ITEM:usize = 2;
+/// Structure that represents a stronghold registered with the dragon depot
+///
+/// # Fields
+/// - `name`: The name of the stronghold
+/// - `recipe`: The resources that the stronghold needs
+/// - `signal`: A signal for the stronghold that its resources are available
+struct Registration {
+    name: String,
+    recipe: Recipe,
+    signal: Arc<Signal>
+}
 
 /// Structure that represents a Depot for the dragon riders to interact with
 ///
 /// # Fields
-/// - `collected_item1`: First item collected by the dragon riders
-/// - `collected_ietm2`: Second item collected by the dragon riders
-/// - `item_count`: The amount of items stored in the dragon depot
-/// - `burnstone_signal`: A signal for burnstone stronghold that its resources are available
-/// - `seaplum_signal`: A signal for seaplum stronghold that its resources are available
-/// - `klah_signal`: A signal for klah stronghold that its resources are available
+/// - `collected`: Units of each resource collected by the dragon riders, keyed by the delivery
+///   they are part of
+/// - `strongholds`: Every stronghold that can be signaled, in the order they were registered
+#[derive(Default)]
 pub struct DragonDepot {
-    pub collected_item1: String,
-    pub collected_item2: String,
-    item_count: usize,
-    burnstone_signal: Arc<(Mutex<bool>, Condvar)>,
-    seaplum_signal: Arc<(Mutex<bool>, Condvar)>,
-    klah_signal: Arc<(Mutex<bool>, Condvar)>
+    collected: HashMap<u64, HashMap<Resource, u32>>,
+    strongholds: Vec<Registration>
 }
 
 impl DragonDepot {
     /// Constructs a new `Dragon Depot` instance with the ability to place
-    /// and deplete resources. It also has the ability to check to see if it
-    /// currently has any of the resources.
-    /// 
+    /// and deplete resources. Strongholds must be registered before they can be signaled.
+    pub fn new() -> DragonDepot {
+        DragonDepot::default()
+    }
+
+    /// Registers the recipe of a stronghold and the signal used to notify it.
+    ///
     /// # Arguments
-    /// * `burnstone_signal`: Signal to notify Burnstone Stronghold that its resources are ready.
-    /// * `seaplum_signal`: Signal to notify Seaplum Stronghold that its resources are ready.
-    /// * `klah_signal`: Signal to notify Klah Stronghold that its resources are ready.
-    pub fn new(burnstone_signal:Arc<(Mutex<bool>, Condvar)>,
-               seaplum_signal:Arc<(Mutex<bool>, Condvar)>,
-               klah_signal:Arc<(Mutex<bool>, Condvar)>) -> DragonDepot {
-        DragonDepot {
-            collected_item1: String::new(),
-            collected_item2: String::new(),
-            item_count: 0,
-            burnstone_signal,
-            seaplum_signal,
-            klah_signal
-        }
+    /// * `name`: The name of the stronghold.
+    /// * `recipe`: The resources that the stronghold needs.
+    /// * `signal`: Signal to notify the stronghold that its resources are ready.
+    pub fn register(&mut self, name:String, recipe:Recipe, signal:Arc<Signal>) {
+        self.strongholds.push(Registration { name, recipe, signal });
     }
 
     /// Places a resource in the depot and checks
     /// if it is time to notify the respective stronghold.
     ///
     /// # Parameters
-    /// - `resource`: The type of resource being placed into the depot.
+    /// - `unit`: The unit of a resource being placed into the depot.
     ///
-    /// This method increments the item count and places the resource into an available slot.
-    /// Once two resources are collected, it checks each resource type and signals the
-    /// corresponding stronghold if its resource is available. After signaling,
-    /// it depletes the resources.
-    pub fn place_resource(&mut self, resource:String) {
-        if self.item_count == 0 {
-            self.collected_item1 = resource;
-        } else {
-            self.collected_item2 = resource;
-        }
-        self.item_count += 1;
-        if self.item_count == MAX_ITEM {
-            // If the depot doesn't have burnstone, notifies Burnstone Stronghold
-            if !self.has_burnstone() {
-                let (lock, condvar) = &*self.burnstone_signal;
-                let mut ready = lock.lock().unwrap();
-                *ready = true;
-                condvar.notify_one();
-            // If the depot doesn't have seaplum, notifies Seaplum Stronghold
-            } else if !self.has_seaplum() {
-                let (lock, condvar) = &*self.seaplum_signal;
-                let mut ready = lock.lock().unwrap();
-                *ready = true;
-                condvar.notify_one();
-            // If the depot doesn't have klah, notifies Klah Stronghold
-            } else if !self.has_klah() {
-                let (lock, condvar) = &*self.klah_signal;
-                let mut ready = lock.lock().unwrap();
-                *ready = true;
-                condvar.notify_one();
-            } else {
-                unreachable!()
-            }
-            self.deplete();
+    /// This method adds the unit to the collected items of its delivery, then checks whether they
+    /// satisfy the recipe of the stronghold the delivery was collected for. If they do, that
+    /// stronghold is signaled and the delivery is cleared.
+    pub fn place_resource(&mut self, unit:Unit) {
+        let collected = self.collected.entry(unit.delivery).or_default();
+        *collected.entry(unit.resource).or_insert(0) += 1;
+        let Some(stronghold) = self.strongholds.iter()
+            .find(|stronghold| stronghold.name == unit.stronghold) else {
+            return;
+        };
+        if stronghold.recipe.is_satisfied_by(collected) {
+            stronghold.signal.raise();
+            self.collected.remove(&unit.delivery);
         }
     }
 
-    /// Test to see if klah is one of the collected items
-    fn has_klah(&self) -> bool {
-        self.collected_item1 == String::from("Klah") ||
-        self.collected_item2 == String::from("Klah")
-    }
+}
 
-    /// Test to see if burnstone is one of the collected items
-    fn has_burnstone(&self) -> bool {
-        self.collected_item1 == String::from("Burnstone") ||
-        self.collected_item2 == String::from("Burnstone")
-    }
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
 
-    /// Test to see if seaplum is one of the collected items
-    fn has_seaplum(&self) -> bool {
-        self.collected_item1 == String::from("Seaplum") ||
-        self.collected_item2 == String::from("Seaplum")
-    }
+    use super::DragonDepot;
+    use crate::{context::Context, depot::Unit, recipe::Recipe};
+    use crate::{resource::{Resource, ResourceCatalog}, signal::{Signal, SignalMode}};
 
-    /// Sets the item_count to 0 and makes the
-    /// collected items reset to fresh strings.
-    fn deplete(&mut self) {
-        self.item_count = 0;
-        self.collected_item1 = String::new();
-        self.collected_item2 = String::new();
-    }
+    /// How long to wait for a signal that should already have been raised.
+    const WAIT:Duration = Duration::from_millis(10);
+
+    #[test]
+    fn each_delivery_completes_its_own_stronghold_when_one_recipe_is_part_of_another() {
+        let (context, _captured) = Context::capture();
+        let (x, y) = (Resource::new("X", "needed by both"), Resource::new("Y", "needed by Large"));
+        let catalog = ResourceCatalog::new(vec![x.clone(), y.clone()]).unwrap();
+        let small = Arc::new(Signal::new("Small", SignalMode::Counting, &context));
+        let large = Arc::new(Signal::new("Large", SignalMode::Counting, &context));
+        let mut depot = DragonDepot::new();
+        depot.register("Small".to_string(), Recipe::parse("X", &catalog).unwrap(),
+                       Arc::clone(&small));
+        depot.register("Large".to_string(), Recipe::parse("X + Y", &catalog).unwrap(),
+                       Arc::clone(&large));
+        let unit = |resource:&Resource, delivery:u64, stronghold:&str| Unit {
+            resource: resource.clone(),
+            delivery,
+            stronghold: stronghold.to_string()
+        };
 
-}
\ No newline at end of file
+        depot.place_resource(unit(&x, 1, "Large"));
+        assert!(!small.wait_timeout(WAIT));
+        depot.place_resource(unit(&x, 2, "Small"));
+        assert!(small.wait_timeout(WAIT));
+        assert!(!large.wait_timeout(WAIT));
+        depot.place_resource(unit(&y, 1, "Large"));
+        assert!(large.wait_timeout(WAIT));
+        assert!(!small.wait_timeout(WAIT));
+    }
+}
@@ -6,13 +6,24 @@
 //! - the type of resource that the dragonrider is carrying
 //! - the depot to obtain resources
 //! - the depot to store resources obtained by the dragonriders
+//!
 //! and has a signal that the depot has resources that are ready to be collected
 //! The dragon rider has the capability to wait and obtain resources, wait and consume resources,
 //! and group resources.
 //!
+//! Every dragon rider of a resource waits on the same counting signal, which the steward raises
+//! once for every unit it places in the depot. Each raise lets one dragon rider through to take
+//! one unit, so a pool of dragon riders shares the work. A dragon rider that finds the unit
+//! already taken by another one counts it as contention and goes back to waiting. A dragon rider
+//! that panics while carrying a unit puts it back in the depot for another one to carry.
+//!
 //! ## Dependencies
 //! This module depends on the `Depot` and `DragonDepot` for resource management, and uses
-//! synchronization primitives from the Rust standard library (`Arc`, `Mutex`, `Condvar`).
+//! synchronization primitives from the Rust standard library (`Arc`, `Mutex`) and `Signal`. The
+//! shared `Context` supplies the logger, the clock, the shutdown token, and the statistics. Both
+//! depots are locked with `crate::sync::lock`, and the dragon rider implements `Runnable` so the
+//! supervisor can run it.
+//! How long each flight takes is drawn from a `crate::timing::Distribution` with `rand`.
 //!
 //! ## Authors
 //! - Dylan Miller
@@ -21,9 +32,11 @@
 //! ## Instructor
 //! - Dr. William Kreahling
 
-use std::sync::{Arc, Condvar, Mutex};
-use crate::{depot::Depot, dragondepot::DragonDepot};
-use crate::logger::Logger;
+use std::sync::{Arc, Mutex};
+use rand::rngs::SmallRng;
+use crate::{depot::{Depot, Unit}, dragondepot::DragonDepot, timing::Distribution};
+use crate::{context::Context, event::{thread_name, EventKind}, resource::Resource, signal::Signal};
+use crate::{supervisor::Runnable, sync::lock};
 
 /// Structure that represents a Dragon Rider that will carry resources to the depot
 ///
@@ -31,106 +44,237 @@ use crate::logger::Logger;
 /// - `resource_type`: The type of resource that the dragonrider is carrying
 /// - `depot`: A reference to the depot to obtain resources from
 /// - `dragon_depot`: A reference to the dragon depot that stores resources from the dragonriders
-/// - `depot_signal`: A signal that the depot has resources that are ready to be collected
-/// - `writer`: Used to print status onto Stdout or a file
+/// - `depot_signal`: A signal that the depot has resources that are ready to be collected, shared
+///   by every dragon rider of the resource
+/// - `fly`: The distribution that how long flying each unit to the dragon depot takes is drawn
+///   from
+/// - `rng`: The random number generator used to pick how long each flight takes
+/// - `context`: Used to log events onto Stdout or a file, tell the clock when the dragon rider
+///   waits on its signal, and notice when the simulation shuts down
 pub struct DragonRider {
-    resource_type: String,
+    resource_type: Resource,
     depot: Arc<Mutex<Depot>>,
     dragon_depot: Arc<Mutex<DragonDepot>>,
-    depot_signal: Arc<(Mutex<bool>, Condvar)>,
-    writer: Arc<Mutex<Logger>>
+    depot_signal: Arc<Signal>,
+    fly: Distribution,
+    rng: SmallRng,
+    context: Context
+}
+
+/// Structure that represents a unit that a dragon rider took from the depot and is carrying
+///
+/// A dragon rider that panics before placing the unit in the dragon depot would lose it, and with
+/// it the room the unit holds in the depot and the rest of its delivery. A unit that is dropped
+/// before it is placed is put back in the depot instead, and the signal raised again, so another
+/// dragon rider carries it.
+///
+/// # Fields
+/// - `unit`: The unit being carried, until it is placed in the dragon depot
+/// - `depot`: The depot the unit was taken from
+/// - `depot_signal`: The signal that lets a dragon rider through to take the unit
+struct Carried {
+    unit: Option<Unit>,
+    depot: Arc<Mutex<Depot>>,
+    depot_signal: Arc<Signal>
+}
+
+impl Carried {
+    /// Places the unit in the dragon depot.
+    ///
+    /// # Arguments
+    /// * `dragon_depot`: The dragon depot the unit is placed in.
+    fn place(mut self, dragon_depot:&Mutex<DragonDepot>) {
+        if let Some(unit) = self.unit.take() {
+            lock(dragon_depot).place_resource(unit);
+        }
+    }
+}
+
+impl Drop for Carried {
+    /// Puts the unit back in the depot if it was never placed in the dragon depot.
+    fn drop(&mut self) {
+        if let Some(unit) = self.unit.take() {
+            lock(&self.depot).put_back(unit);
+            self.depot_signal.raise();
+        }
+    }
 }
 
 impl DragonRider {
     /// Constructs a new `DragonRider` instance with the ability to obtain
     /// and deliver resources.
     /// 
-    /// * `resource`: The name of the resource that uniquely identifies a dragon rider.
+    /// * `resource`: The resource that uniquely identifies a dragon rider.
     /// * `depot`: A reference to the depot where the dragon rider takes resources from.
     /// * `dragon_depot`: A reference to the mini depot where the dragon rider drops off their
-    /// resources.
+    ///   resources.
     /// * `depot_signal`: A signal that notifies the dragon rider that their resource is available
-    /// in the depot.
-    /// * `writer`: The logger to write status info to.
-    pub fn new(resource:String,
+    ///   in the depot.
+    /// * `fly`: The distribution that how long flying each unit to the dragon depot takes is
+    ///   drawn from.
+    /// * `rng`: The random number generator used to pick how long each flight takes.
+    /// * `context`: The logger, clock, and shutdown token shared by every actor.
+    pub fn new(resource:Resource,
                depot:Arc<Mutex<Depot>>,
                dragon_depot:Arc<Mutex<DragonDepot>>,
-               depot_signal:Arc<(Mutex<bool>, Condvar)>,
-               writer:Arc<Mutex<Logger>>) -> DragonRider {
+               depot_signal:Arc<Signal>,
+               fly:Distribution,
+               rng:SmallRng,
+               context:Context) -> DragonRider {
         DragonRider {
             resource_type: resource,
             depot,
             dragon_depot,
             depot_signal,
-            writer
+            fly,
+            rng,
+            context
         }
     }
 
-    /// Logs a message indicating the Dragon Rider is waiting for a resource to become available.
-    fn waiting_for_resource(&self) -> String {
-        self.resource_type.clone() + " dragon rider is waiting for resource"
+    /// Returns the resource that the Dragon Rider carries.
+    pub fn resource(&self) -> &Resource {
+        &self.resource_type
+    }
+
+    /// Returns an event indicating the Dragon Rider is waiting for a resource to become available.
+    fn waiting_for_resource(&self) -> EventKind {
+        EventKind::RiderWaiting { resource: self.resource_type.name().to_string() }
+    }
+
+    /// Returns an event indicating the Dragon Rider has obtained the resource.
+    fn obtained_resource(&self) -> EventKind {
+        EventKind::RiderObtained { resource: self.resource_type.name().to_string() }
     }
 
-    /// Logs a message indicating the Dragon Rider has obtained the resource.
-    fn obtained_resource(&self) -> String {
-        self.resource_type.clone() + " dragon rider has obtained resource"
+    /// Returns an event indicating the Dragon Rider found the depot without its resource.
+    fn found_empty(&self) -> EventKind {
+        EventKind::RiderFoundEmpty { resource: self.resource_type.name().to_string() }
     }
 
-    /// Retrieves a resource from the main depot,
+    /// Retrieves one unit of a resource from the main depot,
     /// based on the type of resource the Dragon Rider handles.
-    fn consume(&self) {
-        let lock = &*self.depot;
-        let mut depot = lock.lock().unwrap();
-        match self.resource_type.as_str() {
-            "Burnstone" => {
-                let _ = &depot.take_burnstone();
-            },
-            "Seaplum" => {
-                let _ = &depot.take_seaplum();
-            },
-            "Klah" => {
-                let _ = &depot.take_klah();
-            }
-            _ => { unreachable!() }
-        }
+    ///
+    /// # Returns
+    /// The unit of the resource that was obtained, or `None` if another dragon rider already took
+    /// it.
+    fn consume(&self) -> Option<Carried> {
+        let taken = lock(&self.depot).take(&self.resource_type);
+        let (name, rider) = (self.resource_type.name(), thread_name());
+        let Some(unit) = taken else {
+            self.write_status(self.found_empty());
+            self.context.record(|stats| stats.record_found_empty(name, &rider));
+            return None;
+        };
+        let carried = Carried {
+            unit: Some(unit),
+            depot: Arc::clone(&self.depot),
+            depot_signal: Arc::clone(&self.depot_signal)
+        };
         self.write_status(self.obtained_resource());
+        self.context.record(|stats| stats.record_obtained(name, &rider));
+        Some(carried)
     }
 
-    /// Writes a status message to the logger.
+    /// Writes an event to the logger.
     /// 
     /// # Arguments
-    /// * `message`: The message that is being written to the logger.
-    fn write_status(&self, message:String) {
-        let lock = &*self.writer;
-        let mut writer = lock.lock().unwrap();
-        writer.write(message);
+    /// * `event`: The event that is being written to the logger.
+    fn write_status(&self, event:EventKind) {
+        self.context.log(event);
     }
 
     /// Waits for a signal that indicates resources are ready for consumption.
-    fn wait_for_consumation(&self) {
-        let (lock, condvar) = &*self.depot_signal;
-        let guard = lock.lock().unwrap();
+    ///
+    /// # Returns
+    /// True if resources are ready, false if the simulation is shutting down.
+    fn wait_for_consumation(&self) -> bool {
         self.write_status(self.waiting_for_resource());
-        let mut guard = condvar.wait_while(guard, |condition| {
-            !*condition
-        }).unwrap();
-        *guard = false;
+        self.depot_signal.wait()
+    }
+
+    /// Flies the obtained resource to the dragon depot and places it there.
+    ///
+    /// # Arguments
+    /// * `carried`: The unit of the resource obtained from the main depot.
+    fn group_resources(&mut self, carried:Carried) {
+        let time = self.fly.sample(&mut self.rng);
+        self.context.clock.sleep(time);
+        carried.place(&self.dragon_depot);
+        self.context.progress.progressed();
     }
+}
 
-    /// Places the obtained resource into the dragon depot.
-    fn group_resources(&mut self) {
-        let lock = &*self.dragon_depot;
-        let mut dragon_depot = lock.lock().unwrap();
-        dragon_depot.place_resource(self.resource_type.clone());
+impl Runnable for DragonRider {
+    /// Main operation flow of the Dragon Rider; coordinates waiting, consuming, and grouping resources
+    /// until the simulation shuts down.
+    fn go(&mut self) {
+        while !self.context.shutdown.is_triggered() {
+            if !self.wait_for_consumation() {
+                break;
+            }
+            if let Some(carried) = self.consume() {
+                self.group_resources(carried);
+            }
+        }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, panic::{self, AssertUnwindSafe}, time::Duration};
+    use std::sync::{Arc, Mutex};
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::DragonRider;
+    use crate::{clock::Clock, context::Context, depot::{Depot, Unit}, dragondepot::DragonDepot};
+    use crate::{logthread::LogHandle, resource::Resource, shutdown::Shutdown};
+    use crate::{signal::{Signal, SignalMode}, supervisor::Runnable, sync::lock};
+    use crate::timing::Distribution;
+
+    /// A clock that panics whenever an actor sleeps, such as a dragon rider flying a unit.
+    struct Falling;
+
+    impl Clock for Falling {
+        fn now(&self) -> Duration {
+            Duration::ZERO
+        }
 
-    /// Main operation flow of the Dragon Rider; coordinates waiting, consuming, and grouping resources.
-    pub fn go(&mut self) {
-        loop {
-            self.wait_for_consumation();
-            self.consume();
-            self.group_resources();
+        fn sleep(&self, _duration:Duration) {
+            panic!("the dragon fell out of the sky");
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn a_unit_is_put_back_when_its_dragon_rider_panics() {
+        let (logger, _captured) = LogHandle::capture();
+        let context = Context::new(logger, Arc::new(Falling), Arc::new(Shutdown::new()));
+        let resource = Resource::new("X", "the resource X");
+        let depot = Arc::new(Mutex::new(Depot::new(HashMap::from([(resource.clone(), 1)]))));
+        let signal = Arc::new(Signal::new("X dragon rider: resource ready", SignalMode::Counting,
+                                          &context));
+        let stronghold = "First".to_string();
+        let unit = Unit { resource: resource.clone(), delivery: 1, stronghold };
+        lock(&depot).place(unit.clone()).unwrap();
+        signal.raise();
+
+        let mut rider = DragonRider::new(resource.clone(),
+                                         Arc::clone(&depot),
+                                         Arc::new(Mutex::new(DragonDepot::new())),
+                                         Arc::clone(&signal),
+                                         Distribution::Fixed { seconds: 1.0 },
+                                         SmallRng::seed_from_u64(1),
+                                         context);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| rider.go())).is_err());
+
+        let mut depot = lock(&depot);
+        assert_eq!(depot.stock(&resource), 1);
+        assert_eq!(depot.held(&resource), 0);
+        // Another dragon rider is let through to carry the unit, and the depot still has room for
+        // one unit once it has been received
+        assert!(signal.wait_timeout(Duration::from_millis(10)));
+        assert_eq!(depot.take(&resource), Some(unit.clone()));
+        depot.release(&[resource]);
+        assert!(depot.place(unit).is_ok());
+    }
+}
@@ -0,0 +1,443 @@
+//! # Hanging on by a Thread: event.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the events that the actors log instead of preformatted strings. Every event holds:
+//! - the time on the simulation's clock when it happened
+//! - the name of the thread that logged it
+//! - the actor it is about
+//! - how important it is, from trace up to warn
+//! - what happened, along with the resources and strongholds involved
+//!
+//! Since events are typed, logs can be filtered, counted, and parsed by machines. Displaying an
+//! event gives the line that is written to the console or the log file: the time, level, and
+//! thread it was logged at, followed by a sentence describing what happened. An event can also be
+//! written as a single line of JSON, for feeding runs into analysis tools.
+//!
+//! ## Dependencies
+//! - `std::fmt` for displaying events as sentences.
+//! - `std::str::FromStr` for parsing levels and kinds of actor from the command line.
+//! - `std::thread` for naming the thread that logged an event.
+//! - `std::time::Duration` for the time an event happened at.
+//! - `serde::Serialize` and `serde_json` for writing events as JSON.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{fmt, str::FromStr, thread, time::Duration};
+use serde::Serialize;
+
+/// How important an event is, from least to most important.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    /// Waiting on a signal, which happens constantly.
+    Trace,
+    /// Finishing an operation or handling a single unit of a resource.
+    Debug,
+    /// Deliveries, services, and the start of each operation.
+    Info,
+    /// Something went wrong, such as the depot being full.
+    Warn
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Trace => f.pad("TRACE"),
+            Level::Debug => f.pad("DEBUG"),
+            Level::Info => f.pad("INFO"),
+            Level::Warn => f.pad("WARN")
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(level:&str) -> Result<Level, String> {
+        match level.to_lowercase().as_str() {
+            "trace" => Ok(Level::Trace),
+            "debug" => Ok(Level::Debug),
+            "info" => Ok(Level::Info),
+            "warn" => Ok(Level::Warn),
+            _ => Err(format!("unknown level '{}', expected trace, debug, info or warn", level))
+        }
+    }
+}
+
+/// The kinds of actor that log events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActorKind {
+    Simulation,
+    Steward,
+    #[serde(rename = "rider")]
+    DragonRider,
+    Stronghold
+}
+
+impl FromStr for ActorKind {
+    type Err = String;
+
+    fn from_str(kind:&str) -> Result<ActorKind, String> {
+        match kind {
+            "simulation" => Ok(ActorKind::Simulation),
+            "steward" => Ok(ActorKind::Steward),
+            "rider" => Ok(ActorKind::DragonRider),
+            "stronghold" => Ok(ActorKind::Stronghold),
+            _ => Err(format!("unknown actor '{}', expected simulation, steward, rider or \
+                              stronghold", kind))
+        }
+    }
+}
+
+impl fmt::Display for ActorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActorKind::Simulation => write!(f, "simulation"),
+            ActorKind::Steward => write!(f, "steward"),
+            ActorKind::DragonRider => write!(f, "rider"),
+            ActorKind::Stronghold => write!(f, "stronghold")
+        }
+    }
+}
+
+/// Structure that represents the actor an event is about
+///
+/// # Fields
+/// - `kind`: The kind of actor
+/// - `name`: The name that tells actors of the same kind apart, such as the resource of a rider
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Actor {
+    pub kind: ActorKind,
+    pub name: String
+}
+
+impl Actor {
+    /// Constructs a new `Actor`.
+    ///
+    /// # Arguments
+    /// * `kind`: The kind of actor.
+    /// * `name`: The name that tells actors of the same kind apart.
+    pub fn new(kind:ActorKind, name:&str) -> Actor {
+        Actor { kind, name: name.to_string() }
+    }
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind, self.name)
+    }
+}
+
+/// What happened during the simulation.
+///
+/// As JSON, the name of the variant is written as `kind`, next to the fields of the variant.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The simulation started with the given seed.
+    SimulationStarted { seed: u64 },
+    /// A resource of the world was loaded.
+    ResourceListed { resource: String, description: String },
+    /// How fairly the strongholds have been served so far, and which one went the longest without
+    /// being served.
+    FairnessMeasured { index: Option<f64>, starved: Option<String>, unserved_seconds: f64 },
+    /// A steward delivered resources to the depot.
+    StewardDelivered { steward: String, resources: Vec<String> },
+    /// A steward could not deliver a resource to the depot.
+    StewardDeliveryFailed { steward: String, resource: String, reason: String },
+    /// A steward is waiting for a stronghold to collect the supplies.
+    StewardWaiting { steward: String },
+    /// A steward is ready to collect more resources.
+    StewardReady { steward: String },
+    /// A steward is waiting for a stronghold to free room in the depot for its resources.
+    StewardBlocked { steward: String, resources: Vec<String> },
+    /// A dragon rider is waiting for its resource.
+    RiderWaiting { resource: String },
+    /// A dragon rider obtained a unit of its resource from the depot.
+    RiderObtained { resource: String },
+    /// A dragon rider found none of its resource in the depot.
+    RiderFoundEmpty { resource: String },
+    /// A stronghold is waiting for the resources of its recipe.
+    StrongholdWaiting { stronghold: String, recipe: String },
+    /// The dragon riders delivered the resources of a stronghold.
+    StrongholdReceived { stronghold: String },
+    /// A stronghold started distributing resources.
+    StrongholdDistributing { stronghold: String },
+    /// A stronghold finished distributing resources.
+    StrongholdDistributed { stronghold: String },
+    /// A stronghold started consuming resources.
+    StrongholdConsuming { stronghold: String },
+    /// A stronghold finished consuming resources.
+    StrongholdConsumed { stronghold: String },
+    /// A signal was raised while it was already raised, so the raise was lost.
+    SignalCoalesced { signal: String, pending: u32 },
+    /// A counting signal was raised while an earlier raise was still waiting to be taken.
+    SignalBacklogged { signal: String, pending: u32 },
+    /// No actor has made progress for longer than the watchdog allows.
+    SimulationStalled { idle_seconds: f64 },
+    /// An actor has made no progress since the simulation stalled, and may be waiting on a signal.
+    ActorStalled { stalled: String, signal: Option<String>, idle_seconds: f64 },
+    /// The thread of an actor panicked.
+    ActorPanicked { crashed: String, message: String },
+    /// An actor whose thread panicked was started again on a new thread.
+    ActorRestarted { restarted: String, restarts: u32 },
+    /// An actor whose thread panicked was not started again.
+    ActorAbandoned { abandoned: String, restarts: u32 }
+}
+
+impl EventKind {
+    /// Returns how important the event is.
+    pub fn level(&self) -> Level {
+        match self {
+            EventKind::StewardWaiting { .. } |
+            EventKind::StewardBlocked { .. } |
+            EventKind::RiderWaiting { .. } |
+            EventKind::StrongholdWaiting { .. } => Level::Trace,
+            EventKind::StewardReady { .. } |
+            EventKind::RiderObtained { .. } |
+            EventKind::RiderFoundEmpty { .. } |
+            EventKind::StrongholdDistributed { .. } |
+            EventKind::StrongholdConsumed { .. } => Level::Debug,
+            EventKind::SimulationStarted { .. } |
+            EventKind::ResourceListed { .. } |
+            EventKind::FairnessMeasured { .. } |
+            EventKind::StewardDelivered { .. } |
+            EventKind::StrongholdReceived { .. } |
+            EventKind::StrongholdDistributing { .. } |
+            EventKind::StrongholdConsuming { .. } => Level::Info,
+            EventKind::StewardDeliveryFailed { .. } |
+            EventKind::SignalCoalesced { .. } |
+            EventKind::SignalBacklogged { .. } |
+            EventKind::SimulationStalled { .. } |
+            EventKind::ActorStalled { .. } |
+            EventKind::ActorPanicked { .. } |
+            EventKind::ActorRestarted { .. } |
+            EventKind::ActorAbandoned { .. } => Level::Warn
+        }
+    }
+
+    /// Returns the actor that the event is about.
+    pub fn actor(&self) -> Actor {
+        match self {
+            EventKind::SimulationStarted { .. } |
+            EventKind::ResourceListed { .. } |
+            EventKind::FairnessMeasured { .. } => Actor::new(ActorKind::Simulation, "main"),
+            EventKind::SignalCoalesced { signal, .. } |
+            EventKind::SignalBacklogged { signal, .. } => Actor::new(ActorKind::Simulation, signal),
+            EventKind::SimulationStalled { .. } |
+            EventKind::ActorStalled { .. } => Actor::new(ActorKind::Simulation, "watchdog"),
+            EventKind::ActorPanicked { .. } |
+            EventKind::ActorRestarted { .. } |
+            EventKind::ActorAbandoned { .. } => Actor::new(ActorKind::Simulation, "supervisor"),
+            EventKind::StewardDelivered { steward, .. } |
+            EventKind::StewardDeliveryFailed { steward, .. } |
+            EventKind::StewardWaiting { steward } |
+            EventKind::StewardReady { steward } |
+            EventKind::StewardBlocked { steward, .. } => Actor::new(ActorKind::Steward, steward),
+            EventKind::RiderWaiting { resource } |
+            EventKind::RiderObtained { resource } |
+            EventKind::RiderFoundEmpty { resource } => Actor::new(ActorKind::DragonRider, resource),
+            EventKind::StrongholdWaiting { stronghold, .. } |
+            EventKind::StrongholdReceived { stronghold } |
+            EventKind::StrongholdDistributing { stronghold } |
+            EventKind::StrongholdDistributed { stronghold } |
+            EventKind::StrongholdConsuming { stronghold } |
+            EventKind::StrongholdConsumed { stronghold } => {
+                Actor::new(ActorKind::Stronghold, stronghold)
+            }
+        }
+    }
+}
+
+/// Returns the name of the current thread, or its id if it has no name.
+pub fn thread_name() -> String {
+    let current = thread::current();
+    match current.name() {
+        Some(name) => name.to_string(),
+        None => format!("{:?}", current.id())
+    }
+}
+
+/// Lists resources as "A, B and C".
+///
+/// # Arguments
+/// * `resources`: The names of the resources.
+fn listed(resources:&[String]) -> String {
+    match resources.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => rest.join(", ") + " and " + last,
+        None => String::new()
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventKind::SimulationStarted { seed } => write!(f, "Simulation seed: {}", seed),
+            EventKind::ResourceListed { resource, description } => {
+                if description.is_empty() {
+                    write!(f, "Resource {}", resource)
+                } else {
+                    write!(f, "Resource {}: {}", resource, description)
+                }
+            },
+            EventKind::FairnessMeasured { index: None, .. } => {
+                write!(f, "No stronghold has been served yet")
+            },
+            EventKind::FairnessMeasured { index: Some(index), starved, unserved_seconds } => {
+                write!(f, "Jain's fairness index of the strongholds is {:.3}", index)?;
+                match starved {
+                    Some(starved) => write!(f, ", and Stronghold {} went the longest without being \
+                                           served ({:.1} seconds)", starved, unserved_seconds),
+                    None => Ok(())
+                }
+            },
+            EventKind::StewardDelivered { resources, .. } => {
+                write!(f, "The steward has delievered resources {} to the depot", listed(resources))
+            },
+            EventKind::StewardDeliveryFailed { reason, .. } => {
+                write!(f, "The steward could not deliever to the depot: {}", reason)
+            },
+            EventKind::StewardWaiting { .. } => {
+                write!(f, "The steward is waiting for stronghold to collect supplies")
+            },
+            EventKind::StewardReady { .. } => {
+                write!(f, "Steward is now ready to collect resources to give to the depot")
+            },
+            EventKind::StewardBlocked { resources, .. } => {
+                write!(f, "The steward is waiting for room in the depot for resources {}",
+                       listed(resources))
+            },
+            EventKind::RiderWaiting { resource } => {
+                write!(f, "{} dragon rider is waiting for resource", resource)
+            },
+            EventKind::RiderObtained { resource } => {
+                write!(f, "{} dragon rider has obtained resource", resource)
+            },
+            EventKind::RiderFoundEmpty { resource } => {
+                write!(f, "{} dragon rider found no resource in the depot", resource)
+            },
+            EventKind::StrongholdWaiting { stronghold, recipe } => {
+                write!(f, "Stronghold {} waiting for its resources ({})", stronghold, recipe)
+            },
+            EventKind::StrongholdReceived { stronghold } => {
+                write!(f, "Dragon riders had delievered resources to Stronghold {}", stronghold)
+            },
+            EventKind::StrongholdDistributing { stronghold } => {
+                write!(f, "Stronghold {} is now distributing resources", stronghold)
+            },
+            EventKind::StrongholdDistributed { stronghold } => {
+                write!(f, "Stronghold {} has finished distributing resources", stronghold)
+            },
+            EventKind::StrongholdConsuming { stronghold } => {
+                write!(f, "Stronghold {} is now consuming resources", stronghold)
+            },
+            EventKind::StrongholdConsumed { stronghold } => {
+                write!(f, "Stronghold {} has finished consuming resources", stronghold)
+            },
+            EventKind::SignalCoalesced { signal, pending } => {
+                write!(f, "Signal {} was raised again before it was waited on, so {} raises will \
+                           only wake one waiter", signal, pending)
+            },
+            EventKind::SignalBacklogged { signal, pending } => {
+                write!(f, "Signal {} was raised again before it was waited on, so {} raises are \
+                           waiting to be taken", signal, pending)
+            },
+            EventKind::SimulationStalled { idle_seconds } => {
+                write!(f, "No actor has made progress for {:.1} seconds", idle_seconds)
+            },
+            EventKind::ActorStalled { stalled, signal: Some(signal), idle_seconds } => {
+                write!(f, "Thread {} has made no progress for {:.1} seconds, waiting on signal {}",
+                       stalled, idle_seconds, signal)
+            },
+            EventKind::ActorStalled { stalled, signal: None, idle_seconds } => {
+                write!(f, "Thread {} has made no progress for {:.1} seconds, not waiting on a signal",
+                       stalled, idle_seconds)
+            },
+            EventKind::ActorPanicked { crashed, message } => {
+                write!(f, "Thread {} panicked: {}", crashed, message)
+            },
+            EventKind::ActorRestarted { restarted, restarts } => {
+                write!(f, "Thread {} was restarted, {} time(s) so far", restarted, restarts)
+            },
+            EventKind::ActorAbandoned { abandoned, restarts } => {
+                write!(f, "Thread {} was not restarted after {} restart(s)", abandoned, restarts)
+            }
+        }
+    }
+}
+
+/// Structure that represents how an event is written as JSON
+///
+/// # Fields
+/// - `timestamp`: The time on the simulation's clock when the event happened, in seconds
+/// - `thread`: The name of the thread that logged the event
+/// - `level`: How important the event is
+/// - `actor`: The actor that the event is about
+/// - `kind`: What happened, flattened into the kind and the fields of the kind
+/// - `message`: The sentence describing what happened
+#[derive(Serialize)]
+struct JsonEvent<'a> {
+    timestamp: f64,
+    thread: &'a str,
+    level: Level,
+    actor: &'a Actor,
+    #[serde(flatten)]
+    kind: &'a EventKind,
+    message: String
+}
+
+/// Structure that represents something that happened during the simulation
+///
+/// # Fields
+/// - `timestamp`: The time on the simulation's clock when the event happened
+/// - `thread`: The name of the thread that logged the event
+/// - `actor`: The actor that the event is about
+/// - `kind`: What happened
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub timestamp: Duration,
+    pub thread: String,
+    pub actor: Actor,
+    pub kind: EventKind
+}
+
+impl Event {
+    /// Constructs a new `Event` logged by the current thread.
+    ///
+    /// # Arguments
+    /// * `timestamp`: The time on the simulation's clock when the event happened.
+    /// * `kind`: What happened.
+    pub fn new(timestamp:Duration, kind:EventKind) -> Event {
+        Event { timestamp, thread: thread_name(), actor: kind.actor(), kind }
+    }
+
+    /// Returns the event as a single line of JSON, such as
+    /// `{"timestamp":12.0,"thread":"rider-Klah","level":"debug","actor":{"kind":"rider",
+    /// "name":"Klah"},"kind":"rider_obtained","resource":"Klah",
+    /// "message":"Klah dragon rider has obtained resource"}`.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&JsonEvent {
+            timestamp: self.timestamp.as_secs_f64(),
+            thread: &self.thread,
+            level: self.kind.level(),
+            actor: &self.actor,
+            kind: &self.kind,
+            message: self.kind.to_string()
+        })
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:>8.1}s {:<5} {}] {}", self.timestamp.as_secs_f64(), self.kind.level(),
+               self.thread, self.kind)
+    }
+}
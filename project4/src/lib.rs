@@ -0,0 +1,53 @@
+//! # Hanging on by a Thread: lib.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! The library behind our program, so that other tools and tests can embed the simulation
+//! instead of running the binary. A `SimulationBuilder` builds the stewards, the dragon riders,
+//! the strongholds, and both depots from a scenario with their signals wired up, and returns a
+//! `Simulation` that can be started, inspected while it runs, and stopped:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use project4::{scenario::Scenario, simulation::SimulationBuilder};
+//!
+//! let mut simulation = SimulationBuilder::new(Scenario::classic())
+//!     .seed(42)
+//!     .virtual_time(true)
+//!     .build()
+//!     .unwrap();
+//! simulation.start().unwrap();
+//! simulation.run_for(Duration::from_secs(60)).unwrap();
+//! let report = simulation.stop();
+//! print!("{}", report.stats);
+//! ```
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+pub mod steward;
+pub mod dragonrider;
+pub mod stronghold;
+pub mod depot;
+pub mod dragondepot;
+pub mod logger;
+pub mod logthread;
+pub mod resource;
+pub mod recipe;
+pub mod clock;
+pub mod shutdown;
+pub mod signal;
+pub mod context;
+pub mod stats;
+pub mod event;
+pub mod timing;
+pub mod scenario;
+pub mod simulation;
+pub mod watchdog;
+pub mod supervisor;
+pub mod supply;
+pub mod sync;
@@ -2,17 +2,31 @@
 //! # Version: May 3rd 2024
 //!
 //! ## Description
-//! This module provides conditional logging to either a file or standard output based on
-//! command line arguments.
-//! The `Logger` structure within this module is what allows for messages to be logged either to a
-//! specified file or to the console.
+//! This module provides logging to files and standard output based on command line arguments.
+//! The `Logger` structure within this module fans every event out to one or more `Sink`s. Each
+//! sink writes either to a specified file or to the console (coloured by level if wanted), either
+//! as sentences or as JSON Lines (one JSON object per event), and has its own filter, so a run
+//! can be watched live while a full machine-readable record is captured.
+//!
+//! A log file can be replaced or appended to. It can also be given a maximum size, in which case
+//! it is rotated once full: `log.txt` is moved to `log.1.txt` (after moving `log.1.txt` to
+//! `log.2.txt`, and so on) and a new `log.txt` is started, so no events are lost.
+//!
+//! Events less important than the level of their kind of actor are left out of a sink.
+//!
+//! Events are buffered and only reach the file when the logger is flushed; the log thread flushes
+//! after every batch of events.
 //!
 //! ## Dependencies
 //! This module relies on several components from the Rust standard library:
 //! - `std::io::{BufWriter, Write, Error}` for handling buffered writing operations,
 //!   which improve performance when writing to files by reducing the number of write operations.
-//! - `std::fs::{File, OpenOptions}` for file management, allowing the logger to create,
-//!   open, and modify files as needed.
+//! - `std::fs::{self, File, OpenOptions}` for file management, allowing the logger to create,
+//!   open, append to, and rotate files as needed.
+//! - `std::path::PathBuf` for the path of the log file and of its rotations.
+//! - `crate::event::Event` for the events that are logged.
+//! - `std::str::FromStr` for parsing the log format from the command line.
+//! - `std::collections::HashMap` for the level each kind of actor is logged at.
 //! - `std::process` for handling critical failures during logging, such as exiting the
 //!   application when a file write fails, ensuring that file writing errors are not silently ignored.
 //!
@@ -24,63 +38,474 @@
 //! - Dr. William Kreahling
 
 use std::io::{BufWriter,Write,Error};
-use std::fs::{File,OpenOptions};
-use std::process;
+use std::fs::{self,File,OpenOptions};
+use std::path::PathBuf;
+use std::{collections::HashMap, process, str::FromStr};
+use crate::event::{ActorKind, Event, Level};
+
+/// The formats that events can be logged in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One sentence per event, meant to be read by people.
+    #[default]
+    Text,
+    /// One JSON object per event, meant to be read by analysis tools.
+    Jsonl
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(format:&str) -> Result<LogFormat, String> {
+        match format {
+            "text" => Ok(LogFormat::Text),
+            "jsonl" => Ok(LogFormat::Jsonl),
+            _ => Err(format!("unknown log format '{}', expected text or jsonl", format))
+        }
+    }
+}
 
-/// Represents a simple logger with the capability to write messages to a file
-/// or standard output.
+/// Structure that represents which events are logged
 ///
 /// # Fields
-/// - `file_writer`: An optional `BufWriter<File>` used for writing messages to a file
-///   when enabled. If `None`, messages will be printed to standard output.
-pub struct Logger {
-    file_writer: Option<BufWriter<File>>
+/// - `level`: The least important level that is logged
+/// - `actor_levels`: The least important level that is logged for a kind of actor, overriding
+///   `level`
+#[derive(Clone, Debug)]
+pub struct LogFilter {
+    level: Level,
+    actor_levels: HashMap<ActorKind, Level>
 }
 
-impl Logger {
-    /// Creates a new `Logger` instance, optionally writing to a file.
+impl Default for LogFilter {
+    /// Logs every event.
+    fn default() -> LogFilter {
+        LogFilter::new(Level::Trace)
+    }
+}
+
+impl LogFilter {
+    /// Constructs a new `LogFilter` that logs events of every actor at or above `level`.
     ///
     /// # Arguments
-    /// - `file_name`: The path to the file where logs should be written.
-    /// - `write_to_file`: A boolean flag that, if true, initializes file writing.
+    /// - `level`: The least important level that is logged.
+    pub fn new(level: Level) -> LogFilter {
+        LogFilter { level, actor_levels: HashMap::new() }
+    }
+
+    /// Sets the least important level that is logged.
+    ///
+    /// # Arguments
+    /// - `level`: The least important level that is logged for actors without their own level.
+    pub fn set_level(&mut self, level: Level) {
+        self.level = level;
+    }
+
+    /// Sets the least important level that is logged for a kind of actor.
+    ///
+    /// # Arguments
+    /// - `kind`: The kind of actor.
+    /// - `level`: The least important level that is logged for the kind of actor.
+    pub fn set_actor_level(&mut self, kind: ActorKind, level: Level) {
+        self.actor_levels.insert(kind, level);
+    }
+
+    /// Test to see if an event should be logged.
+    ///
+    /// # Arguments
+    /// - `event`: The event that may be logged.
+    pub fn allows(&self, event: &Event) -> bool {
+        let level = self.actor_levels.get(&event.actor.kind).unwrap_or(&self.level);
+        event.kind.level() >= *level
+    }
+}
+
+/// Structure that represents the file that a logger writes to
+///
+/// # Fields
+/// - `path`: The path to the file where logs should be written
+/// - `append`: Whether events are added to the end of an existing file instead of replacing it
+/// - `max_bytes`: The size the file may grow to before it is rotated, if it is rotated at all
+#[derive(Clone, Debug)]
+pub struct LogFile {
+    path: PathBuf,
+    append: bool,
+    max_bytes: Option<u64>
+}
+
+impl LogFile {
+    /// Constructs a new `LogFile`.
+    ///
+    /// # Arguments
+    /// - `path`: The path to the file where logs should be written.
+    /// - `append`: Whether events are added to the end of an existing file instead of replacing it.
+    /// - `max_bytes`: The size the file may grow to before it is rotated, if it is rotated at all.
+    pub fn new(path: PathBuf, append: bool, max_bytes: Option<u64>) -> LogFile {
+        LogFile { path, append, max_bytes }
+    }
+
+    /// Opens the file for writing.
+    ///
+    /// # Arguments
+    /// - `append`: Whether events are added to the end of the file instead of replacing it.
+    ///
+    /// # Returns
+    /// - The opened file and how many bytes it already holds.
+    fn open(&self, append: bool) -> Result<(File, u64), Error> {
+        let output = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&self.path)?;
+        let size = output.metadata()?.len();
+        Ok((output, size))
+    }
+
+    /// Returns the path of a rotated file, such as `log.2.txt` for `log.txt`.
+    ///
+    /// # Arguments
+    /// - `index`: How many rotations ago the file was written, starting at 1.
+    fn rotated(&self, index: u32) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, index, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, index)
+        };
+        self.path.with_file_name(name)
+    }
+
+    /// Moves every rotated file one index up, then moves the file to index 1, so the file can
+    /// be started over without losing what was written to it.
+    fn rotate(&self) -> Result<(), Error> {
+        let mut last = 1;
+        while self.rotated(last).exists() {
+            last += 1;
+        }
+        for index in (1..last).rev() {
+            fs::rename(self.rotated(index), self.rotated(index + 1))?;
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+}
+
+/// Structure that represents a sink described on the command line, before its file is opened
+///
+/// A sink is described as a target followed by comma separated settings, such as
+/// `console,level=info` or `file=run.jsonl,format=jsonl,rider=trace`. The target is `console` or
+/// `file=<path>`, and the settings are `format=<text|jsonl>`, `level=<level>`, and
+/// `<actor>=<level>` for the level of a kind of actor.
+///
+/// # Fields
+/// - `path`: The file to write to, or `None` for the console
+/// - `format`: The format that events are written in
+/// - `filter`: Which events are written
+#[derive(Clone, Debug)]
+pub struct SinkSpec {
+    path: Option<PathBuf>,
+    format: LogFormat,
+    filter: LogFilter
+}
+
+impl FromStr for SinkSpec {
+    type Err = String;
+
+    fn from_str(spec:&str) -> Result<SinkSpec, String> {
+        let mut parts = spec.split(',');
+        let target = parts.next().unwrap_or_default();
+        let path = match target.split_once('=') {
+            None if target == "console" => None,
+            Some(("file", path)) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => return Err(format!("'{}' must start with console or file=<path>", spec))
+        };
+        let mut sink = SinkSpec { path, format: LogFormat::default(), filter: LogFilter::default() };
+        for setting in parts {
+            let Some((key, value)) = setting.split_once('=') else {
+                return Err(format!("setting '{}' must be <key>=<value>", setting));
+            };
+            match key {
+                "format" => sink.format = value.parse()?,
+                "level" => sink.filter.set_level(value.parse()?),
+                actor => sink.filter.set_actor_level(actor.parse()?, value.parse()?)
+            }
+        }
+        Ok(sink)
+    }
+}
+
+impl SinkSpec {
+    /// Opens the sink.
+    ///
+    /// # Arguments
+    /// - `append`: Whether a file is appended to instead of replaced.
+    /// - `max_bytes`: The size a file may grow to before it is rotated, if it is rotated at all.
+    /// - `colour`: Whether text lines on the console are coloured by level.
     ///
     /// # Returns
-    /// - A `Result<Logger, Error>` which is `Ok` containing the `Logger` if file operations succeed,
+    /// - A `Result<Sink, Error>` which is `Ok` containing the `Sink` if file operations succeed,
     ///   or an `Err` with an `Error` if there is a problem opening or creating the file.
-    pub fn new(file_name: String, write_to_file: bool) -> Result<Logger, Error> {
-        if write_to_file {
-            let output = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(file_name)?;
-            Ok(Logger { file_writer: Some(BufWriter::new(output)) })
-        } else {
-            Ok(Logger { file_writer: None })
+    pub fn open(self, append: bool, max_bytes: Option<u64>, colour: bool) -> Result<Sink, Error> {
+        match self.path {
+            Some(path) => Sink::file(LogFile::new(path, append, max_bytes), self.format, self.filter),
+            None => Ok(Sink::console(self.format, self.filter, colour))
         }
     }
+}
 
-    /// Writes a message to the configured output destination.
+/// Where a sink writes its events.
+///
+/// # Variants
+/// - `Console`: Standard output, with each line coloured by level if `colour` is true
+/// - `File`: A log file, written through `writer`, which holds `written` bytes
+enum Target {
+    Console { colour: bool },
+    File { file: LogFile, writer: BufWriter<File>, written: u64 }
+}
+
+/// Represents one destination of the log: the console or a file, the format that events are
+/// written in there, and which events are written there.
+///
+/// # Fields
+/// - `target`: Where events are written
+/// - `format`: The format that events are written in
+/// - `filter`: Which events are written
+pub struct Sink {
+    target: Target,
+    format: LogFormat,
+    filter: LogFilter
+}
+
+impl Sink {
+    /// Creates a new `Sink` that prints to standard output.
+    ///
+    /// # Arguments
+    /// - `format`: The format that events are written in.
+    /// - `filter`: Which events are written.
+    /// - `colour`: Whether text lines are coloured by level.
+    pub fn console(format: LogFormat, filter: LogFilter, colour: bool) -> Sink {
+        Sink { target: Target::Console { colour }, format, filter }
+    }
+
+    /// Creates a new `Sink` that writes to a file.
+    ///
+    /// # Arguments
+    /// - `file`: The file where events should be written.
+    /// - `format`: The format that events are written in.
+    /// - `filter`: Which events are written.
+    ///
+    /// # Returns
+    /// - A `Result<Sink, Error>` which is `Ok` containing the `Sink` if file operations succeed,
+    ///   or an `Err` with an `Error` if there is a problem opening or creating the file.
+    pub fn file(file: LogFile, format: LogFormat, filter: LogFilter) -> Result<Sink, Error> {
+        let (output, written) = file.open(file.append)?;
+        let target = Target::File { file, writer: BufWriter::new(output), written };
+        Ok(Sink { target, format, filter })
+    }
+
+    /// Writes an event, unless the filter leaves it out.
     ///
-    /// If a file writer is set up, the message will be written to the file. If writing fails,
-    /// the process will exit with an error status. If no file writer is present, the message
-    /// will be printed to standard output.
+    /// A file is rotated first if the event would make it larger than its maximum size, and the
+    /// event stays buffered until the sink is flushed. If writing fails, the process will exit
+    /// with an error status.
+    ///
+    /// # Arguments
+    /// - `event`: The event to write.
+    ///
+    /// # Panics
+    /// - The function will exit the process if it fails to write to or rotate the file, or to
+    ///   write the event as JSON.
+    fn log(&mut self, event: &Event) {
+        if !self.filter.allows(event) {
+            return;
+        }
+        let line = match self.format {
+            LogFormat::Text => event.to_string(),
+            LogFormat::Jsonl => match event.to_json() {
+                Ok(json) => json,
+                Err(e) => {
+                    eprintln!("Error writing event as JSON: {}", e);
+                    process::exit(1);
+                }
+            }
+        };
+        match &mut self.target {
+            Target::Console { colour: true } if self.format == LogFormat::Text => {
+                println!("{}{}{}", colour_of(event.kind.level()), line, RESET);
+            },
+            Target::Console { .. } => println!("{}", line),
+            Target::File { file, writer, written } => {
+                let size = line.len() as u64 + 1;
+                if file.max_bytes.is_some_and(|max| *written > 0 && *written + size > max) {
+                    if let Err(e) = Sink::rotate(file, writer, written) {
+                        eprintln!("Error rotating log file: {}", e);
+                        process::exit(1);
+                    }
+                }
+                if let Err(e) = writeln!(writer, "{}", line) {
+                    eprintln!("Error writing to file: {}", e);
+                    process::exit(1);
+                }
+                *written += size;
+            }
+        }
+    }
+
+    /// Closes the file, moves it aside as `log.1.txt` (shifting older rotations up), and starts
+    /// a new, empty file in its place.
     ///
     /// # Arguments
-    /// - `message`: The string message to log.
+    /// - `file`: The file being rotated.
+    /// - `writer`: The writer of the file, which is replaced by a writer of the new file.
+    /// - `written`: How many bytes the file holds, which is reset for the new file.
+    fn rotate(file: &LogFile, writer: &mut BufWriter<File>, written: &mut u64) -> Result<(), Error> {
+        writer.flush()?;
+        file.rotate()?;
+        let (output, size) = file.open(false)?;
+        *writer = BufWriter::new(output);
+        *written = size;
+        Ok(())
+    }
+
+    /// Flushes any buffered events to the file.
     ///
     /// # Panics
-    /// - The function will exit the process if it fails to write to the file.
-    pub fn write(&mut self, message: String) {
-        if let Some(ref mut writer) = self.file_writer {
-            if let Err(e) = writeln!(writer, "{}", message) {
+    /// - The function will exit the process if it fails to flush the file.
+    fn flush(&mut self) {
+        if let Target::File { writer, .. } = &mut self.target {
+            if let Err(e) = writer.flush() {
                 eprintln!("Error writing to file: {}", e);
                 process::exit(1);
             }
-            writer.flush().unwrap();
-        } else {
-            println!("{}", message);
+        }
+    }
+}
+
+/// The ANSI escape code that ends a coloured line.
+const RESET:&str = "\x1b[0m";
+
+/// Returns the ANSI escape code that colours a line of the given level.
+///
+/// # Arguments
+/// - `level`: The level of the event on the line.
+fn colour_of(level: Level) -> &'static str {
+    match level {
+        Level::Trace => "\x1b[90m",
+        Level::Debug => "\x1b[36m",
+        Level::Info => "\x1b[32m",
+        Level::Warn => "\x1b[33m"
+    }
+}
+
+/// Represents a logger that fans every event out to one or more sinks, such as a coloured
+/// console, a plain text file, and a JSON Lines file.
+///
+/// # Fields
+/// - `sinks`: Every destination that events are written to.
+pub struct Logger {
+    sinks: Vec<Sink>
+}
+
+impl Logger {
+    /// Creates a new `Logger` instance that writes to every sink.
+    ///
+    /// # Arguments
+    /// - `sinks`: Every destination that events are written to.
+    pub fn new(sinks: Vec<Sink>) -> Logger {
+        Logger { sinks }
+    }
+
+    /// Writes an event to every sink whose filter lets it through.
+    ///
+    /// # Arguments
+    /// - `event`: The event to log.
+    ///
+    /// # Panics
+    /// - The function will exit the process if it fails to write to a file.
+    pub fn log(&mut self, event: &Event) {
+        for sink in &mut self.sinks {
+            sink.log(event);
+        }
+    }
+
+    /// Flushes any buffered events to every file.
+    ///
+    /// # Panics
+    /// - The function will exit the process if it fails to flush a file.
+    pub fn flush(&mut self) {
+        for sink in &mut self.sinks {
+            sink.flush();
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process, time::Duration};
+
+    use super::{LogFile, LogFormat, SinkSpec};
+    use crate::event::{Event, EventKind};
+
+    /// Returns an event of a dragon rider at the debug level.
+    fn rider_obtained() -> Event {
+        Event::new(Duration::ZERO, EventKind::RiderObtained { resource: "Klah".to_string() })
+    }
+
+    /// Returns an event of a stronghold at the info level.
+    fn stronghold_received() -> Event {
+        Event::new(Duration::ZERO, EventKind::StrongholdReceived { stronghold: "Klah".to_string() })
+    }
+
+    #[test]
+    fn a_sink_spec_reads_its_target_and_settings() {
+        let console:SinkSpec = "console".parse().unwrap();
+        assert_eq!(console.path, None);
+        assert_eq!(console.format, LogFormat::Text);
+        assert!(console.filter.allows(&rider_obtained()));
+
+        let file:SinkSpec = "file=run.jsonl,format=jsonl,level=info,rider=debug".parse().unwrap();
+        assert_eq!(file.path, Some(PathBuf::from("run.jsonl")));
+        assert_eq!(file.format, LogFormat::Jsonl);
+        assert!(file.filter.allows(&rider_obtained()));
+        assert!(file.filter.allows(&stronghold_received()));
+
+        let quiet:SinkSpec = "console,level=warn".parse().unwrap();
+        assert!(!quiet.filter.allows(&rider_obtained()));
+        assert!(!quiet.filter.allows(&stronghold_received()));
+    }
+
+    #[test]
+    fn a_sink_spec_rejects_unknown_targets_and_settings() {
+        for spec in ["", "stdout", "file", "file=", "console,level", "console,format=xml",
+                     "console,level=loud", "console,dragon=info", "file=run.txt,rider=loud"] {
+            assert!(spec.parse::<SinkSpec>().is_err(), "{} was accepted", spec);
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn rotated_files_are_numbered_before_the_extension() {
+        let file = LogFile::new(PathBuf::from("logs/log.txt"), false, None);
+        assert_eq!(file.rotated(1), PathBuf::from("logs/log.1.txt"));
+        assert_eq!(file.rotated(12), PathBuf::from("logs/log.12.txt"));
+        let bare = LogFile::new(PathBuf::from("log"), false, None);
+        assert_eq!(bare.rotated(2), PathBuf::from("log.2"));
+    }
+
+    #[test]
+    fn rotating_moves_every_older_file_one_index_up() {
+        let directory = std::env::temp_dir().join(format!("project4-rotate-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let file = LogFile::new(directory.join("log.txt"), false, None);
+        for contents in ["oldest", "older", "newest"] {
+            fs::write(&file.path, contents).unwrap();
+            file.rotate().unwrap();
+        }
+        assert!(!file.path.exists());
+        let rotated:Vec<String> = (1..=3)
+            .map(|index| fs::read_to_string(file.rotated(index)).unwrap())
+            .collect();
+        assert_eq!(rotated, ["newest", "older", "oldest"]);
+        assert!(!file.rotated(4).exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
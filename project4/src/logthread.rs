@@ -0,0 +1,156 @@
+//! # Hanging on by a Thread: logthread.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the thread that writes the log, so that actors never wait on file I/O:
+//! - `LogHandle` is shared by every actor and sends events over a channel without blocking
+//! - `LogThread` owns the `Logger` and writes the events it receives on a dedicated thread
+//!
+//! Events are written in batches: the thread takes every event waiting in the channel, writes
+//! them, and only flushes once the channel is empty. Stopping the thread drains every event sent
+//! before it was stopped, then flushes the log.
+//!
+//! ## Dependencies
+//! - `std::sync::mpsc::{self, Receiver, Sender}` for sending events to the log thread.
+//! - `std::thread::{self, JoinHandle}` for running and joining the log thread.
+//! - `crate::event::Event` for the events that are logged.
+//! - `crate::logger::Logger` for writing the events.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::{event::Event, logger::Logger};
+
+/// The messages sent to the log thread.
+enum Message {
+    /// An event to write to the log.
+    Event(Event),
+    /// Tells the log thread to flush the log and stop.
+    Stop
+}
+
+/// Structure that represents the sending end of the log, shared by every actor
+///
+/// # Fields
+/// - `sender`: The channel that events are sent to the log thread over
+#[derive(Clone)]
+pub struct LogHandle {
+    sender: Sender<Message>
+}
+
+impl LogHandle {
+    /// Sends an event to the log thread without waiting for it to be written.
+    ///
+    /// Events sent after the log thread has stopped are dropped.
+    ///
+    /// # Arguments
+    /// * `event`: The event to log.
+    pub fn log(&self, event:Event) {
+        let _ = self.sender.send(Message::Event(event));
+    }
+}
+
+#[cfg(test)]
+impl LogHandle {
+    /// Constructs a handle whose events are kept instead of sent to a log thread, so a test can
+    /// read what was logged.
+    ///
+    /// # Returns
+    /// The handle, and the events that are sent with it.
+    pub(crate) fn capture() -> (LogHandle, Captured) {
+        let (sender, receiver) = mpsc::channel();
+        (LogHandle { sender }, Captured { receiver })
+    }
+}
+
+/// Structure that represents the events sent with a handle made by `LogHandle::capture`
+///
+/// # Fields
+/// - `receiver`: The channel that the events were sent over
+#[cfg(test)]
+pub(crate) struct Captured {
+    receiver: Receiver<Message>
+}
+
+#[cfg(test)]
+impl Captured {
+    /// Returns every event sent since this was last called.
+    pub(crate) fn events(&self) -> Vec<Event> {
+        self.receiver.try_iter().filter_map(|message| match message {
+            Message::Event(event) => Some(event),
+            Message::Stop => None
+        }).collect()
+    }
+}
+
+/// Structure that represents the thread that writes the log
+///
+/// # Fields
+/// - `handle`: A handle for sending events to the thread
+/// - `thread`: The thread that writes the log
+pub struct LogThread {
+    handle: LogHandle,
+    thread: JoinHandle<()>
+}
+
+impl LogThread {
+    /// Spawns the log thread, which writes every event it receives with `logger`.
+    ///
+    /// # Arguments
+    /// * `logger`: The logger that events are written with.
+    ///
+    /// # Returns
+    /// The log thread, or an `Err` if the thread could not be spawned.
+    pub fn spawn(logger:Logger) -> std::io::Result<LogThread> {
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("logger".to_string())
+            .spawn(move || LogThread::run(logger, receiver))?;
+        Ok(LogThread { handle: LogHandle { sender }, thread })
+    }
+
+    /// Returns a handle for sending events to the thread.
+    pub fn handle(&self) -> LogHandle {
+        self.handle.clone()
+    }
+
+    /// Writes events in batches until the thread is stopped or every handle is dropped.
+    ///
+    /// # Arguments
+    /// * `logger`: The logger that events are written with.
+    /// * `receiver`: The channel that events are received over.
+    fn run(mut logger:Logger, receiver:Receiver<Message>) {
+        while let Ok(message) = receiver.recv() {
+            let mut batch = Some(message);
+            while let Some(message) = batch {
+                match message {
+                    Message::Event(event) => logger.log(&event),
+                    Message::Stop => {
+                        logger.flush();
+                        return;
+                    }
+                }
+                batch = receiver.try_recv().ok();
+            }
+            logger.flush();
+        }
+        logger.flush();
+    }
+
+    /// Stops the thread once it has written every event sent before this call, and waits for
+    /// it to finish.
+    ///
+    /// # Returns
+    /// False if the log thread had panicked.
+    pub fn stop(self) -> bool {
+        let _ = self.handle.sender.send(Message::Stop);
+        self.thread.join().is_ok()
+    }
+}
@@ -4,14 +4,19 @@
 //! ## Description
 //! The entry point to our program. This file holds the logic for
 //! taking command line arguments, logging to either the terminal
-//! or to a file, then setting up all structures and threads.
+//! or to a file, then building and running the simulation from the library.
 //!
 //! ## Dependencies
 //! This module depends on the following external crates:
-//! - use std::{env, process}
-//! - use std::sync::{Arc, Condvar, Mutex}
-//! - use std::thread
+//! - use std::{fs, process}
+//! - use std::path::Path
+//! - use std::io::{self, IsTerminal}
 //! - use std::time::Duration
+//! - project4, for building and running the simulation
+//! - clap, through the cli module, for parsing the command line
+//! - ctrlc, for shutting down gracefully when Ctrl-C is pressed
+//! - std::fs, for writing the statistics of the run as JSON
+//! - rand, for the seed that both runs share when comparing the steward modes
 //!
 //! ## Authors
 //! - Dylan Miller
@@ -21,199 +26,250 @@
 //! - Dr. William Kreahling
 
 
-pub mod steward;
-pub mod dragonrider;
-pub mod stronghold;
-mod depot;
-pub mod dragondepot;
-mod logger;
+mod cli;
 
-use std::{env, process, sync::{Arc, Mutex, Condvar}, thread, time::Duration};
-use depot::Depot;
-use logger::Logger;
-use steward::Steward;
-use dragonrider::DragonRider;
-use stronghold::Stronghold;
-use crate::dragondepot::DragonDepot;
+use std::{fs, process};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::time::Duration;
+use cli::Cli;
+use project4::logger::{LogFile, Logger, Sink};
+use project4::scenario::Scenario;
+use project4::simulation::{Report, Simulation, SimulationBuilder};
+use project4::stats::Comparison;
+use project4::steward::StewardMode;
 
-/// Constant that contains the name of the file to log to when running
-const LOG_FILE:&str = "log.txt";
+/// Constant that contains the exit code used when the watchdog stops a stalled simulation
+const STALLED_EXIT_CODE:i32 = 2;
 
 /// The entry point for our program
 ///
 /// Sets up logic for taking command line arguments,
 /// logging to either the terminal or a file,
+/// loading the scenario that describes the world,
 /// setting up the different structures,
 /// then creating all the threads,
-/// then running until ctrl + c is pressed or until time runs out.
+/// then running until ctrl + c is pressed or until time runs out,
+/// or running the scenario in both steward modes to compare them.
 ///
 fn main() {
-    let args:Vec<String> = env::args().collect();
-    // If size of arguments is not equal to 3, prints usage statement and exits program
-    if args.len() != 3 {
-        println!("Usage: cargo run <seconds_to_run> <T|F>");
-        process::exit(1);
+    // Settings given on the command line
+    let cli = Cli::parse_args();
+    // Get number of seconds to run process for
+    let seconds = cli.duration();
+    if cli.compare_modes {
+        compare_modes(&cli, seconds);
+        return;
     }
-    // Get number of seconds to run process for from first command line argument
-    let seconds = get_seconds(&args[1]);
     // Determines if status information is printed to Stdout or to a log file
-    let logger = get_logger(&args[2]);
-
-    // A reference to the logger for printing status information
-    let log_arc = Arc::new(Mutex::new(logger));
-
-    // Depot where the steward stores resources to/the dragon riders take from
-    let depot = Arc::new(Mutex::new(Depot::new()));
-
-    // Signal for steward to collect supplies after stronghold is finished
-    let steward_signal = Arc::new((Mutex::new(false), Condvar::new()));
-    // Signal from steward that burnstone is supplied in the depot
-    let burnstone_signal = Arc::new((Mutex::new(false), Condvar::new()));
-    // Signal from steward that seaplum is supplied in the depot
-    let seaplum_signal = Arc::new((Mutex::new(false), Condvar::new()));
-    // Signal from steward that klah is supplied in the depot
-    let klah_signal = Arc::new((Mutex::new(false), Condvar::new()));
-    // Signal from dragon rider's depot that supplies for burnstone stronghold is ready
-    let burnstone_stronghold_signal = Arc::new((Mutex::new(false), Condvar::new()));
-    // Signal from dragon rider's depot that supplies for seaplum stronghold is ready
-    let seaplum_stronghold_signal = Arc::new((Mutex::new(false), Condvar::new()));
-    // Signal from dragon rider's depot that supplies for klah stronghold is ready
-    let klah_stronghold_signal = Arc::new((Mutex::new(false), Condvar::new()));
-
-    // Mini depot that dragon riders put resources in to know which stronghold to deliever to
-    let dragon_depot = Arc::new(Mutex::new(DragonDepot::new(
-        Arc::clone(&burnstone_stronghold_signal), Arc::clone(&seaplum_stronghold_signal),
-        Arc::clone(&klah_stronghold_signal)
-    )));
-
-    // Steward
-    let steward = Steward::new(
-        Arc::clone(&depot), Arc::clone(&steward_signal), Arc::clone(&burnstone_signal),
-        Arc::clone(&seaplum_signal), Arc::clone(&klah_signal), Arc::clone(&log_arc)
-    );
-
-    // List of strongholds
-    let mut strongholds:Vec<Stronghold> = Vec::new();
-    // Burnstone Stronghold
-    let burnstone_stronghold = Stronghold::new(
-        "Burnstone".to_string(), Arc::clone(&steward_signal),
-        Arc::clone(&burnstone_stronghold_signal), Arc::clone(&log_arc)
-    );
-    strongholds.push(burnstone_stronghold);
-    // Seaplum Stronghold
-    let seaplum_stronghold = Stronghold::new(
-        "Seaplum".to_string(), Arc::clone(&steward_signal),
-        Arc::clone(&seaplum_stronghold_signal), Arc::clone(&log_arc)
-    );
-    strongholds.push(seaplum_stronghold);
-    // Klah Stronghold
-    let klah_stronghold = Stronghold::new(
-        "Klah".to_string(), Arc::clone(&steward_signal), Arc::clone(&klah_stronghold_signal),
-        Arc::clone(&log_arc)
-    );
-    strongholds.push(klah_stronghold);
-
-    // List of dragon riders
-    let mut dragonriders:Vec<DragonRider> = Vec::new();
-    // Dragon Rider for Burnstone resource
-    let burnstone_dragon_rider = DragonRider::new(
-        "Burnstone".to_string(), Arc::clone(&depot), Arc::clone(&dragon_depot),
-        Arc::clone(&burnstone_signal), Arc::clone(&log_arc)
-    );
-    dragonriders.push(burnstone_dragon_rider);
-    // Dragon Rider for Seaplum resource
-    let seaplum_dragon_rider = DragonRider::new(
-        "Seaplum".to_string(), Arc::clone(&depot), Arc::clone(&dragon_depot),
-        Arc::clone(&seaplum_signal), Arc::clone(&log_arc)
-    );
-    dragonriders.push(seaplum_dragon_rider);
-    // Dragon Rider for Klah resource
-    let klah_dragon_rider = DragonRider::new(
-        "Klah".to_string(), Arc::clone(&depot), Arc::clone(&dragon_depot),
-        Arc::clone(&klah_signal), Arc::clone(&log_arc)
-    );
-    dragonriders.push(klah_dragon_rider);
+    let logger = get_logger(&cli);
+    // The resources, strongholds, dragon riders, depot, and timing of the world
+    let mut builder = get_builder(&cli).logger(logger);
+    if let Some(mode) = cli.steward_mode {
+        builder = builder.steward_mode(mode);
+    }
+    let mut simulation = build_simulation(builder);
+    // Shuts every actor down when Ctrl-C is pressed
+    let shutdown = simulation.shutdown();
+    if let Err(e) = ctrlc::set_handler(move || shutdown.trigger()) {
+        eprintln!("Could not listen for Ctrl-C: {}", e);
+    }
 
     // Spawn threads
-    spawn_threads(steward, strongholds, dragonriders);
-
+    start_simulation(&mut simulation);
     // Runs main process for amount of time inputted by the user
-    run_main_thread(seconds);
+    run_main_thread(seconds, cli.fairness_every, &mut simulation);
 
+    // Tells every thread to stop, then waits for them to finish
+    let report = simulation.stop();
+    if !report.log_complete {
+        eprintln!("The log thread panicked, so the log may be incomplete");
+    }
+    print_summary(&report);
+    if let Some(path) = &cli.stats_json {
+        write_stats_json(path, &report);
+    }
+    if report.stalled && cli.watchdog_abort {
+        eprintln!("The simulation stalled, so it was stopped early");
+        process::exit(STALLED_EXIT_CODE);
+    }
 }
 
-/// Get the number of seconds given by an user.
-/// 
-/// Prints an error message and exits out of the program if user inputted a non integer datatype
-/// as the first command line argument.
-/// 
+/// Returns a builder for the scenario with the settings given on the command line, other than
+/// the log and how the stewards pace their deliveries.
+///
 /// # Arguments
-/// * `argument`: The first command line argument inputted by an user.
-/// 
-/// # Return
-/// An integer that represents what the amount of seconds that the user entered.
-/// 
-/// # Panics
-/// The program exits if the argument cannot be parsed correctly.
-fn get_seconds(argument:&String) -> i64 {
-    let seconds_convert = argument.parse::<i64>();
-    if seconds_convert.is_err() {
-        eprintln!("Invalid argument for number of seconds: must be an integer");
+/// * `cli`: The settings that pick the scenario file, seed, clock, dragon riders, supply policy,
+///   watchdog, and restart policy.
+fn get_builder(cli:&Cli) -> SimulationBuilder {
+    let mut builder = SimulationBuilder::new(get_scenario(&cli.world_file()))
+        .virtual_time(cli.virtual_time)
+        .restart(cli.restart);
+    if let Some(seed) = cli.seed {
+        builder = builder.seed(seed);
+    }
+    if let Some(riders) = cli.riders {
+        builder = builder.riders(riders);
+    }
+    if let Some(supply) = cli.supply {
+        builder = builder.supply(supply);
+    }
+    if let Some(window) = cli.watchdog {
+        builder = builder.watchdog(Duration::from_secs(window), cli.watchdog_abort);
+    }
+    builder
+}
+
+/// Builds the simulation, printing an error message and exiting out of the program if the
+/// scenario can't be simulated.
+///
+/// # Arguments
+/// * `builder`: The scenario and settings of the run.
+fn build_simulation(builder:SimulationBuilder) -> Simulation {
+    match builder.build() {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("Could not build the simulation: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Spawns every thread of the simulation, printing an error message and exiting out of the
+/// program if one could not be spawned.
+///
+/// # Arguments
+/// * `simulation`: The simulation to start.
+fn start_simulation(simulation:&mut Simulation) {
+    if let Err(e) = simulation.start() {
+        eprintln!("Could not start the simulation: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Lets a started simulation run for an amount of time on its clock, printing an error message
+/// and exiting out of the program if it is not running.
+///
+/// # Arguments
+/// * `simulation`: The simulation to run.
+/// * `duration`: How long to let the simulation run for.
+fn run_simulation(simulation:&Simulation, duration:Duration) {
+    if let Err(e) = simulation.run_for(duration) {
+        eprintln!("Could not run the simulation: {}", e);
         process::exit(1);
     }
-    seconds_convert.unwrap()
 }
 
-/// Returns a logger that either prints to Stdout or to the log file.
+/// Runs the scenario once with the stewards in lock step and once pipelined, with the same seed
+/// and without a log, then prints how the throughput of the two runs compares.
+///
+/// Prints an error message and exits out of the program if no duration was given, since both
+/// runs have to end on their own, or if the world has more than one steward, since several
+/// stewards can't work in lock step.
+///
+/// # Arguments
+/// * `cli`: The settings of both runs.
+/// * `seconds`: The number of seconds that each run lasts.
+fn compare_modes(cli:&Cli, seconds:i64) {
+    if seconds <= 0 {
+        eprintln!("Comparing the steward modes needs a duration, such as --duration 600");
+        process::exit(1);
+    }
+    let world_file = cli.world_file();
+    // Several stewards can only be pipelined, so there would be no lock step run to compare with
+    let stewards = get_scenario(&world_file).stewards().len();
+    if stewards > 1 {
+        eprintln!("Comparing the steward modes needs a world with a single steward, but {} has {}",
+                  world_file.display(), stewards);
+        process::exit(1);
+    }
+    let seed = cli.seed.unwrap_or_else(rand::random);
+    let mut reports = Vec::new();
+    for mode in [StewardMode::Lockstep, StewardMode::Pipelined] {
+        let builder = get_builder(cli).seed(seed).steward_mode(mode);
+        let mut simulation = build_simulation(builder);
+        start_simulation(&mut simulation);
+        run_simulation(&simulation, Duration::from_secs(seconds as u64));
+        reports.push(simulation.stop());
+    }
+
+    println!("Compared the steward modes over {} seconds with seed {}", seconds, seed);
+    for report in &reports {
+        println!("{}: joined {} threads ({} panicked, {} restarted){}", report.steward_mode,
+                 report.threads, report.panicked, report.restarts,
+                 if report.stalled { ", stalled" } else { "" });
+    }
+    println!();
+    let (lockstep, pipelined) = (&reports[0], &reports[1]);
+    print!("{}", Comparison::new((lockstep.steward_mode.to_string(), &lockstep.stats),
+                                 (pipelined.steward_mode.to_string(), &pipelined.stats)));
+    if cli.watchdog_abort && reports.iter().any(|report| report.stalled) {
+        eprintln!("A run stalled, so it was stopped early");
+        process::exit(STALLED_EXIT_CODE);
+    }
+}
+
+/// Returns a logger that prints to Stdout or to the log file, and to every extra sink.
 /// 
-/// Prints error messages and exits out of the program if user inputted an incorrect argument
-/// or if an occurred when creating the logger.
+/// Prints an error message and exits out of the program if an error occurred when creating
+/// the logger.
 /// 
 /// # Arguments
-/// * `argument`: The second command line argument inputted by an user.
+/// * `cli`: The settings that pick the log file, how it is opened and rotated, the format
+///   and filter of events, and the extra sinks.
 /// 
 /// # Return
-/// A logger that prints to Stdout if the user inputted a "F" as their argument or a logger that
-/// prints to a log file if the user inputted a "T" as their argument.
+/// A logger that prints to a log file if one was given with `--log-file` or `T`, or to Stdout
+/// otherwise, as well as to every sink given with `--sink`. The console is coloured by level
+/// when it is a terminal, unless `--no-colour` was given.
 /// 
 /// # Panics
-/// The program exits if the user supplied an argument that isn't a T or a F and if an error 
-/// occurred when creating the log file.
-fn get_logger(argument:&String) -> Logger {
-    if argument.as_str() != "T" && argument.as_str() != "F" {
-        eprintln!("Invalid argument for true or false condition for logging");
-        process::exit(1);
+/// The program exits if an error occurred when creating a log file.
+fn get_logger(cli:&Cli) -> Logger {
+    let colour = !cli.no_colour && io::stdout().is_terminal();
+    let main_sink = match cli.log_file() {
+        Some(path) => {
+            let file = LogFile::new(path, cli.append, cli.log_max_bytes);
+            Sink::file(file, cli.log_format, cli.log_filter())
+        },
+        None => Ok(Sink::console(cli.log_format, cli.log_filter(), colour))
+    };
+    let mut sinks = vec![main_sink];
+    for sink in &cli.sink {
+        sinks.push(sink.clone().open(cli.append, cli.log_max_bytes, colour));
     }
-    let is_writable = if argument.as_str() == "T" { true } else { false };
-    let logger_result = Logger::new(LOG_FILE.to_string(), is_writable);
-    if logger_result.is_err() {
-        eprintln!("File does not exist for logger to write to");
-        process::exit(1);
+    match sinks.into_iter().collect::<Result<Vec<Sink>, _>>() {
+        Ok(sinks) => Logger::new(sinks),
+        Err(e) => {
+            eprintln!("Could not open a log file for the logger to write to: {}", e);
+            process::exit(1);
+        }
     }
-    logger_result.unwrap()
 }
 
-/// Spawns threads to run steward, stronghold, and dragonrider cycle.
-/// 
+/// Returns the scenario described by the scenario file.
+///
+/// If the scenario file does not exist, the classic scenario of burnstone, seaplum, and klah is
+/// used. Prints an error message and exits out of the program if the scenario file is invalid.
+///
 /// # Arguments
-/// * `steward`: The steward to run steward thread.
-/// * `strongholds`: List of strongholds, each spawning their own thread.
-/// * `dragonriders`: List of dragonriders, each spawning their own thread.
-fn spawn_threads(steward:Steward, strongholds:Vec<Stronghold>, dragonriders:Vec<DragonRider>) {
-    // Steward thread
-    let mut steward = steward;
-    thread::spawn(move || {
-        steward.go();
-    });
-    for stronghold in strongholds {
-        thread::spawn(move || {
-            stronghold.go();
-        });
-    }
-    for mut dragonrider in dragonriders {
-        thread::spawn(move || {
-            dragonrider.go();
-        });
+/// * `path`: The path to the scenario file.
+///
+/// # Return
+/// The resources, strongholds, dragon riders, depot, and timing of the world.
+///
+/// # Panics
+/// The program exits if the scenario file could not be read or describes an unusable world.
+fn get_scenario(path:&Path) -> Scenario {
+    if !path.exists() {
+        return Scenario::classic();
+    }
+    match Scenario::load(path) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            eprintln!("Invalid world file {}: {}", path.display(), e);
+            process::exit(1);
+        }
     }
 }
 
@@ -221,13 +277,57 @@ fn spawn_threads(steward:Steward, strongholds:Vec<Stronghold>, dragonriders:Vec<
 /// 
 /// # Arguments
 /// * `seconds`: The number of seconds that the main process runs for. If seconds is less than or
-/// equal to 0, then process runs indefinitely until user kills process with Crtl C.
-fn run_main_thread(seconds: i64) {
-    if seconds > 0 {
-        // Waits for inputted seconds before quiting
-        thread::sleep(Duration::from_secs(seconds as u64));
+///   equal to 0, then process runs indefinitely until user kills process with Crtl C.
+/// * `fairness_every`: How many seconds to wait between logging how fairly the strongholds have
+///   been served, or `None` to not log it.
+/// * `simulation`: The simulation whose clock the seconds are measured on and whose shutdown
+///   token is triggered by Ctrl C.
+fn run_main_thread(seconds: i64, fairness_every:Option<u64>, simulation:&mut Simulation) {
+    if let Some(every) = fairness_every {
+        // Wakes up every so often to log the fairness, until time runs out or Ctrl C is pressed
+        let every = Duration::from_secs(every);
+        let end = (seconds > 0).then(|| Duration::from_secs(seconds as u64));
+        while !simulation.shutdown().is_triggered() {
+            let left = end.map_or(every, |end| end.saturating_sub(simulation.now()));
+            if left.is_zero() {
+                break;
+            }
+            run_simulation(simulation, left.min(every));
+            simulation.log_fairness();
+        }
+    } else if seconds > 0 {
+        // Waits for inputted seconds, or until Ctrl C is pressed, before quiting
+        run_simulation(simulation, Duration::from_secs(seconds as u64));
     } else {
-        // Runs forever until user presses Ctrl C to kill process
-        thread::park();
+        // Runs forever until user presses Ctrl C to stop the process
+        simulation.run_until_shutdown();
+    }
+}
+
+/// Prints a summary of the run once every thread has been joined.
+///
+/// # Arguments
+/// * `report`: What the run did.
+fn print_summary(report:&Report) {
+    println!("Simulation finished after {:.1} seconds", report.elapsed.as_secs_f64());
+    println!("Joined {} threads ({} panicked, {} restarted)",
+             report.threads, report.panicked, report.restarts);
+    println!();
+    print!("{}", report.stats);
+}
+
+/// Writes the statistics of the run to a file as JSON.
+///
+/// Prints an error message if the file could not be written.
+///
+/// # Arguments
+/// * `path`: The path of the file to write to.
+/// * `report`: What the run did, whose statistics are written.
+fn write_stats_json(path:&Path, report:&Report) {
+    let written = report.stats.to_json()
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(path, json + "\n").map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        eprintln!("Could not write statistics to {}: {}", path.display(), e);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,314 @@
+//! # Hanging on by a Thread: recipe.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the recipes that strongholds declare. A recipe is a multiset of resources, such as
+//! "2 Seaplum + 1 Klah", that the dragon riders must bring to a stronghold before it can
+//! distribute and consume them.
+//! Recipes are read from the `[[stronghold]]` tables of the world file. When none are given, each
+//! resource gets the classic stronghold that needs one unit of every other resource.
+//!
+//! ## Dependencies
+//! - `serde::Deserialize` and `toml` for reading the strongholds out of the world file.
+//! - `crate::resource` for looking up the resources named in a recipe.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{collections::{HashMap, HashSet}, fmt, fs, io, path::Path};
+use serde::Deserialize;
+
+use crate::resource::{Resource, ResourceCatalog};
+
+/// Error returned when a recipe cannot be built.
+///
+/// # Variants
+/// - `Io`: The world file could not be read.
+/// - `Parse`: The world file is not valid TOML or is missing fields.
+/// - `Empty`: The recipe of a stronghold has no ingredients.
+/// - `BadCount`: An ingredient has a count that isn't a positive integer.
+/// - `UnknownResource`: An ingredient names a resource that is not in the catalog.
+/// - `DuplicateStronghold`: Two strongholds share the same name.
+#[derive(Debug)]
+pub enum RecipeError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Empty(String),
+    BadCount(String),
+    UnknownResource(String),
+    DuplicateStronghold(String)
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecipeError::Io(e) => write!(f, "could not read world file: {}", e),
+            RecipeError::Parse(e) => write!(f, "could not parse world file: {}", e),
+            RecipeError::Empty(name) => write!(f, "stronghold {} has an empty recipe", name),
+            RecipeError::BadCount(term) => write!(f, "invalid ingredient count in \"{}\"", term),
+            RecipeError::UnknownResource(name) => write!(f, "unknown resource {}", name),
+            RecipeError::DuplicateStronghold(name) => {
+                write!(f, "stronghold {} is listed more than once", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecipeError {}
+
+/// Structure that represents the resources a stronghold needs
+///
+/// # Fields
+/// - `ingredients`: Each resource that is needed and how many units of it, in catalog order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipe {
+    ingredients: Vec<(Resource, u32)>
+}
+
+impl Recipe {
+    /// Parses a recipe written as ingredients joined by `+`, such as "2 Seaplum + 1 Klah".
+    ///
+    /// The count of an ingredient may be left out, in which case it is 1.
+    ///
+    /// # Arguments
+    /// * `text`: The written recipe.
+    /// * `catalog`: Every resource that exists in the world.
+    ///
+    /// # Returns
+    /// An `Err` if a count is invalid or a resource is not in the catalog.
+    pub fn parse(text:&str, catalog:&ResourceCatalog) -> Result<Recipe, RecipeError> {
+        let mut counts:HashMap<&Resource, u32> = HashMap::new();
+        for term in text.split('+').map(str::trim).filter(|term| !term.is_empty()) {
+            let (count, name) = match term.split_once(char::is_whitespace) {
+                Some((count, name)) if count.chars().all(|c| c.is_ascii_digit()) => {
+                    let count = count.parse::<u32>()
+                        .map_err(|_| RecipeError::BadCount(term.to_string()))?;
+                    (count, name.trim())
+                },
+                _ => (1, term)
+            };
+            if count == 0 {
+                return Err(RecipeError::BadCount(term.to_string()));
+            }
+            let resource = catalog.resources().iter()
+                .find(|resource| resource.name() == name)
+                .ok_or_else(|| RecipeError::UnknownResource(name.to_string()))?;
+            *counts.entry(resource).or_insert(0) += count;
+        }
+        Ok(Recipe::from_counts(&counts, catalog))
+    }
+
+    /// Builds the classic recipe of a stronghold: one unit of every resource but its own.
+    ///
+    /// # Arguments
+    /// * `harvested`: The resource that the stronghold harvests itself.
+    /// * `catalog`: Every resource that exists in the world.
+    pub fn classic(harvested:&Resource, catalog:&ResourceCatalog) -> Recipe {
+        Recipe {
+            ingredients: catalog.resources().iter()
+                .filter(|resource| *resource != harvested)
+                .map(|resource| (resource.clone(), 1))
+                .collect()
+        }
+    }
+
+    /// Orders counted resources the way they appear in the catalog.
+    fn from_counts(counts:&HashMap<&Resource, u32>, catalog:&ResourceCatalog) -> Recipe {
+        Recipe {
+            ingredients: catalog.resources().iter()
+                .filter_map(|resource| counts.get(resource).map(|count| (resource.clone(), *count)))
+                .collect()
+        }
+    }
+
+    /// Returns each resource that is needed and how many units of it.
+    pub fn ingredients(&self) -> &[(Resource, u32)] {
+        &self.ingredients
+    }
+
+    /// Returns true if the recipe has no ingredients.
+    pub fn is_empty(&self) -> bool {
+        self.ingredients.is_empty()
+    }
+
+    /// Returns every unit that is needed, with a resource repeated once per unit.
+    pub fn units(&self) -> Vec<Resource> {
+        self.ingredients.iter()
+            .flat_map(|(resource, count)| std::iter::repeat_n(resource.clone(), *count as usize))
+            .collect()
+    }
+
+    /// Test to see if the collected items hold every ingredient of the recipe.
+    ///
+    /// # Arguments
+    /// * `collected`: How many units of each resource have been collected.
+    pub fn is_satisfied_by(&self, collected:&HashMap<Resource, u32>) -> bool {
+        self.ingredients.iter()
+            .all(|(resource, count)| collected.get(resource).copied().unwrap_or(0) >= *count)
+    }
+
+    /// Test to see if every ingredient of another recipe is also needed by this recipe.
+    ///
+    /// # Arguments
+    /// * `other`: The recipe that may be contained in this one.
+    pub fn contains(&self, other:&Recipe) -> bool {
+        let counts:HashMap<Resource, u32> = self.ingredients.iter().cloned().collect();
+        other.is_satisfied_by(&counts)
+    }
+}
+
+impl fmt::Display for Recipe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms:Vec<String> = self.ingredients.iter()
+            .map(|(resource, count)| format!("{} {}", count, resource))
+            .collect();
+        write!(f, "{}", terms.join(" + "))
+    }
+}
+
+/// The layout of a `[[stronghold]]` table in the world file.
+///
+/// # Fields
+/// - `name`: The name of the stronghold
+/// - `recipe`: The written recipe of the stronghold
+#[derive(Deserialize)]
+struct StrongholdEntry {
+    name: String,
+    recipe: String
+}
+
+/// The layout of the stronghold section of the world file.
+///
+/// # Fields
+/// - `strongholds`: Every `[[stronghold]]` table in the file.
+#[derive(Deserialize)]
+struct RecipeFile {
+    #[serde(rename = "stronghold", default)]
+    strongholds: Vec<StrongholdEntry>
+}
+
+/// Structure that represents the recipe of every stronghold in the world
+///
+/// # Fields
+/// - `recipes`: The name of each stronghold and its recipe, in the order they were listed
+#[derive(Debug, Clone)]
+pub struct RecipeBook {
+    recipes: Vec<(String, Recipe)>
+}
+
+impl RecipeBook {
+    /// Constructs a new `RecipeBook` after checking that every stronghold is usable.
+    ///
+    /// # Arguments
+    /// * `recipes`: The name of each stronghold and its recipe.
+    ///
+    /// # Returns
+    /// An `Err` if a recipe is empty or if two strongholds share a name.
+    pub fn new(recipes:Vec<(String, Recipe)>) -> Result<RecipeBook, RecipeError> {
+        let mut names = HashSet::new();
+        for (name, recipe) in &recipes {
+            if recipe.is_empty() {
+                return Err(RecipeError::Empty(name.clone()));
+            }
+            if !names.insert(name.as_str()) {
+                return Err(RecipeError::DuplicateStronghold(name.clone()));
+            }
+        }
+        Ok(RecipeBook { recipes })
+    }
+
+    /// Builds the classic recipe book with one stronghold named after each resource.
+    ///
+    /// # Arguments
+    /// * `catalog`: Every resource that exists in the world.
+    pub fn classic(catalog:&ResourceCatalog) -> RecipeBook {
+        RecipeBook {
+            recipes: catalog.resources().iter()
+                .map(|resource| (resource.name().to_string(), Recipe::classic(resource, catalog)))
+                .collect()
+        }
+    }
+
+    /// Loads the recipe book from the `[[stronghold]]` tables of a world file.
+    ///
+    /// If the world file has no strongholds, the classic recipe book is used.
+    ///
+    /// # Arguments
+    /// * `path`: The path to the world file.
+    /// * `catalog`: Every resource that exists in the world.
+    pub fn load(path:&Path, catalog:&ResourceCatalog) -> Result<RecipeBook, RecipeError> {
+        let contents = fs::read_to_string(path).map_err(RecipeError::Io)?;
+        let file:RecipeFile = toml::from_str(&contents).map_err(RecipeError::Parse)?;
+        if file.strongholds.is_empty() {
+            return Ok(RecipeBook::classic(catalog));
+        }
+        let mut recipes = Vec::new();
+        for entry in file.strongholds {
+            let recipe = Recipe::parse(&entry.recipe, catalog)?;
+            recipes.push((entry.name, recipe));
+        }
+        RecipeBook::new(recipes)
+    }
+
+    /// Returns the name of each stronghold and its recipe.
+    pub fn recipes(&self) -> &[(String, Recipe)] {
+        &self.recipes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recipe, RecipeBook, RecipeError};
+    use crate::resource::ResourceCatalog;
+
+    /// Parses a recipe of the classic catalog of burnstone, seaplum, and klah.
+    ///
+    /// # Arguments
+    /// * `text`: The written recipe.
+    fn parse(text:&str) -> Result<Recipe, RecipeError> {
+        Recipe::parse(text, &ResourceCatalog::default())
+    }
+
+    #[test]
+    fn a_recipe_is_counted_in_catalog_order() {
+        let recipe = parse("Klah + 2 Seaplum + Klah").unwrap();
+        assert_eq!(recipe.to_string(), "2 Seaplum + 2 Klah");
+        let units:Vec<String> = recipe.units().iter().map(ToString::to_string).collect();
+        assert_eq!(units, ["Seaplum", "Seaplum", "Klah", "Klah"]);
+        assert!(parse(" + ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_counts_and_unknown_resources_are_rejected() {
+        assert!(matches!(parse("0 Klah"), Err(RecipeError::BadCount(term)) if term == "0 Klah"));
+        assert!(matches!(parse("99999999999 Klah"), Err(RecipeError::BadCount(_))));
+        assert!(matches!(parse("2 Gold"),
+                         Err(RecipeError::UnknownResource(name)) if name == "Gold"));
+        assert!(matches!(parse("Klah + -1 Seaplum"), Err(RecipeError::UnknownResource(_))));
+    }
+
+    #[test]
+    fn a_recipe_book_rejects_empty_recipes_and_repeated_strongholds() {
+        let klah = parse("Klah").unwrap();
+        let empty = parse("").unwrap();
+        assert!(matches!(RecipeBook::new(vec![("Kitchen".to_string(), empty)]),
+                         Err(RecipeError::Empty(name)) if name == "Kitchen"));
+        let repeated = vec![("Kitchen".to_string(), klah.clone()), ("Kitchen".to_string(), klah)];
+        assert!(matches!(RecipeBook::new(repeated),
+                         Err(RecipeError::DuplicateStronghold(name)) if name == "Kitchen"));
+    }
+
+    #[test]
+    fn a_recipe_contains_every_recipe_it_needs_at_least_as_much_of() {
+        let larger = parse("2 Seaplum + Klah").unwrap();
+        assert!(larger.contains(&parse("Seaplum").unwrap()));
+        assert!(larger.contains(&parse("2 Seaplum + Klah").unwrap()));
+        assert!(!larger.contains(&parse("3 Seaplum").unwrap()));
+        assert!(!larger.contains(&parse("Burnstone").unwrap()));
+        assert!(!parse("Seaplum").unwrap().contains(&larger));
+    }
+}
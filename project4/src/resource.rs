@@ -0,0 +1,169 @@
+//! # Hanging on by a Thread: resource.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the resources that exist in the world and the catalog that holds them.
+//! The catalog is loaded at startup from the world file so that the depot, the dragon depot,
+//! the steward, the dragon riders, and the strongholds all work for any number of resources.
+//! When no world file is given the classic resources are used:
+//! - burnstone
+//! - seaplum
+//! - klah
+//!
+//! ## Dependencies
+//! - `serde::Deserialize` and `toml` for reading the resources out of the world file.
+//! - `std::fs` for reading the world file.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{collections::HashSet, fmt, fs, io, path::Path};
+use serde::Deserialize;
+
+/// The minimum amount of resources needed for the steward to have something to withhold.
+const MIN_RESOURCES:usize = 2;
+
+/// Structure that represents a single type of resource
+///
+/// # Fields
+/// - `name`: The name that uniquely identifies the resource
+/// - `description`: A short description of what the resource is used for
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct Resource {
+    name: String,
+    #[serde(default)]
+    description: String
+}
+
+impl Resource {
+    /// Constructs a new `Resource`.
+    ///
+    /// # Arguments
+    /// * `name`: The name that uniquely identifies the resource.
+    /// * `description`: A short description of what the resource is used for.
+    pub fn new(name:&str, description:&str) -> Resource {
+        Resource {
+            name: name.to_string(),
+            description: description.to_string()
+        }
+    }
+
+    /// Returns the name of the resource.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the description of the resource.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Error returned when the resource catalog cannot be built.
+///
+/// # Variants
+/// - `Io`: The world file could not be read.
+/// - `Parse`: The world file is not valid TOML or is missing fields.
+/// - `TooFew`: Fewer resources than `MIN_RESOURCES` were given.
+/// - `Duplicate`: Two resources share the same name.
+#[derive(Debug)]
+pub enum CatalogError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    TooFew(usize),
+    Duplicate(String)
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::Io(e) => write!(f, "could not read world file: {}", e),
+            CatalogError::Parse(e) => write!(f, "could not parse world file: {}", e),
+            CatalogError::TooFew(count) => {
+                write!(f, "at least {} resources are needed, found {}", MIN_RESOURCES, count)
+            },
+            CatalogError::Duplicate(name) => write!(f, "resource {} is listed more than once", name)
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
+
+/// The layout of the resource section of the world file.
+///
+/// # Fields
+/// - `resources`: Every `[[resource]]` table in the file.
+#[derive(Deserialize)]
+struct CatalogFile {
+    #[serde(rename = "resource", default)]
+    resources: Vec<Resource>
+}
+
+/// Structure that represents every resource that exists in the world
+///
+/// # Fields
+/// - `resources`: The resources, in the order they were listed
+#[derive(Debug, Clone)]
+pub struct ResourceCatalog {
+    resources: Vec<Resource>
+}
+
+impl ResourceCatalog {
+    /// Constructs a new `ResourceCatalog` after checking that the resources are usable.
+    ///
+    /// # Arguments
+    /// * `resources`: The resources that exist in the world.
+    ///
+    /// # Returns
+    /// An `Err` if there are too few resources or if two resources share a name.
+    pub fn new(resources:Vec<Resource>) -> Result<ResourceCatalog, CatalogError> {
+        if resources.len() < MIN_RESOURCES {
+            return Err(CatalogError::TooFew(resources.len()));
+        }
+        let mut names = HashSet::new();
+        for resource in &resources {
+            if !names.insert(resource.name()) {
+                return Err(CatalogError::Duplicate(resource.name().to_string()));
+            }
+        }
+        Ok(ResourceCatalog { resources })
+    }
+
+    /// Loads the catalog from the `[[resource]]` tables of a world file.
+    ///
+    /// # Arguments
+    /// * `path`: The path to the world file.
+    pub fn load(path:&Path) -> Result<ResourceCatalog, CatalogError> {
+        let contents = fs::read_to_string(path).map_err(CatalogError::Io)?;
+        let file:CatalogFile = toml::from_str(&contents).map_err(CatalogError::Parse)?;
+        ResourceCatalog::new(file.resources)
+    }
+
+    /// Returns every resource in the catalog.
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+}
+
+impl Default for ResourceCatalog {
+    /// The classic catalog of burnstone, seaplum, and klah.
+    fn default() -> ResourceCatalog {
+        ResourceCatalog {
+            resources: vec![
+                Resource::new("Burnstone", "provides defense and power to the strongholds"),
+                Resource::new("Seaplum", "a delicious and nutritious foodstuff"),
+                Resource::new("Klah", "a nutritious and tasty drink")
+            ]
+        }
+    }
+}
@@ -0,0 +1,508 @@
+//! # Hanging on by a Thread: scenario.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the scenario that the simulation is built from, so experiments can be kept in version
+//! control instead of being compiled in. A scenario file describes:
+//! - the resources of the world, how many dragon riders carry each, and how many units of each
+//!   the depot can hold
+//! - how long a dragon rider spends flying each unit to the dragon depot
+//! - the strongholds, their recipes, and how long they spend distributing and consuming
+//! - the stewards, which strongholds each one supplies, and how long each spends collecting a set
+//!   of resources
+//! - how the stewards pick which stronghold to supply next, and how much each stronghold weighs
+//!
+//! Every section may be left out, in which case the classic world is used: a depot holding 10
+//! units of each resource, one dragon rider per resource that flies instantly, a single steward
+//! that supplies every stronghold at random and collects instantly, and strongholds that spend 5
+//! to 9 seconds on each operation.
+//!
+//! ## Dependencies
+//! - `serde::Deserialize` and `toml` for reading the scenario file.
+//! - `crate::resource` and `crate::recipe` for the resources and strongholds of the world.
+//! - `crate::timing` for how long each operation takes.
+//! - `crate::supply::SupplyKind` for how the stewards pick which stronghold to supply next.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+use serde::Deserialize;
+
+use crate::recipe::{RecipeBook, RecipeError};
+use crate::resource::{CatalogError, Resource, ResourceCatalog};
+use crate::supply::SupplyKind;
+use crate::timing::{Distribution, Timing};
+
+/// Constant that contains the amount of units of each resource the depot can hold, unless the
+/// scenario says otherwise
+pub const DEPOT_CAPACITY:u32 = 10;
+
+/// Constant that contains how many dragon riders carry each resource, unless the scenario says
+/// otherwise
+pub const RIDERS:u32 = 1;
+
+/// Error returned when the scenario file cannot be used.
+///
+/// # Variants
+/// - `Io`: The scenario file could not be read.
+/// - `Parse`: The scenario file is not valid TOML or has a setting of the wrong type.
+/// - `Catalog`: The resources of the scenario are unusable.
+/// - `Recipe`: The strongholds of the scenario are unusable.
+/// - `Invalid`: A setting has a value that can't be used, such as a depot that holds nothing.
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Catalog(CatalogError),
+    Recipe(RecipeError),
+    Invalid { setting: String, reason: String }
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "could not read world file: {}", e),
+            ScenarioError::Parse(e) => write!(f, "could not parse world file: {}", e),
+            ScenarioError::Catalog(e) => write!(f, "{}", e),
+            ScenarioError::Recipe(e) => write!(f, "{}", e),
+            ScenarioError::Invalid { setting, reason } => write!(f, "{}: {}", setting, reason)
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+/// The layout of the settings of a `[[resource]]` table in the scenario file.
+///
+/// # Fields
+/// - `name`: The name of the resource
+/// - `riders`: How many dragon riders carry the resource, if not `[riders] count`
+/// - `capacity`: How many units of the resource the depot holds, if not `[depot] capacity`
+#[derive(Deserialize)]
+struct ResourceEntry {
+    name: String,
+    riders: Option<u32>,
+    capacity: Option<u32>
+}
+
+/// The layout of the timing of a `[[stronghold]]` table in the scenario file.
+///
+/// # Fields
+/// - `name`: The name of the stronghold
+/// - `distribute`: How long the stronghold distributes for, if not `[timing] distribute`
+/// - `consume`: How long the stronghold consumes for, if not `[timing] consume`
+/// - `weight`: How likely the stronghold is to be supplied by the `weighted` supply policy
+#[derive(Deserialize)]
+struct StrongholdEntry {
+    name: String,
+    distribute: Option<Distribution>,
+    consume: Option<Distribution>,
+    weight: Option<u32>
+}
+
+/// The layout of the `[depot]` table in the scenario file.
+///
+/// # Fields
+/// - `capacity`: How many units of each resource the depot holds
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DepotSection {
+    capacity: u32
+}
+
+/// The layout of the `[riders]` table in the scenario file.
+///
+/// # Fields
+/// - `count`: How many dragon riders carry each resource
+/// - `fly`: How long a dragon rider spends flying each unit to the dragon depot
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RidersSection {
+    count: Option<u32>,
+    fly: Option<Distribution>
+}
+
+/// The layout of the `[steward]` table, or of each `[[steward]]` table, in the scenario file.
+///
+/// # Fields
+/// - `name`: The name of the steward
+/// - `collect`: How long the steward spends collecting each set of resources
+/// - `strongholds`: The names of the strongholds the steward supplies, if not every stronghold
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StewardEntry {
+    name: Option<String>,
+    collect: Option<Distribution>,
+    strongholds: Option<Vec<String>>
+}
+
+/// The layout of the `[supply]` table in the scenario file.
+///
+/// # Fields
+/// - `policy`: How the stewards pick which stronghold to supply next
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SupplySection {
+    policy: SupplyKind
+}
+
+/// The layout of the `[timing]` table in the scenario file.
+///
+/// # Fields
+/// - `distribute`: How long every stronghold distributes for
+/// - `consume`: How long every stronghold consumes for
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TimingSection {
+    distribute: Option<Distribution>,
+    consume: Option<Distribution>
+}
+
+/// The layout of the scenario file, besides what the resource catalog and recipe book read.
+///
+/// # Fields
+/// - `resources`: Every `[[resource]]` table in the file
+/// - `strongholds`: Every `[[stronghold]]` table in the file
+/// - `depot`: The `[depot]` table, if there is one
+/// - `riders`: The `[riders]` table, if there is one
+/// - `steward`: The `[steward]` table or the `[[steward]]` tables, if there are any
+/// - `supply`: The `[supply]` table, if there is one
+/// - `timing`: The `[timing]` table, if there is one
+#[derive(Deserialize)]
+struct ScenarioFile {
+    #[serde(rename = "resource", default)]
+    resources: Vec<ResourceEntry>,
+    #[serde(rename = "stronghold", default)]
+    strongholds: Vec<StrongholdEntry>,
+    depot: Option<DepotSection>,
+    riders: Option<RidersSection>,
+    steward: Option<toml::Value>,
+    supply: Option<SupplySection>,
+    timing: Option<TimingSection>
+}
+
+/// Structure that represents one steward of the world
+///
+/// # Fields
+/// - `name`: The name of the steward
+/// - `collect`: How long the steward spends collecting each set of resources
+/// - `strongholds`: The names of the strongholds whose recipes the steward delivers
+#[derive(Clone, Debug)]
+pub struct StewardSettings {
+    pub name: String,
+    pub collect: Distribution,
+    pub strongholds: Vec<String>
+}
+
+/// Structure that represents everything the simulation is built from
+///
+/// # Fields
+/// - `catalog`: Every resource that exists in the world
+/// - `recipes`: The name and recipe of every stronghold in the world
+/// - `capacities`: How many units of each resource the depot holds
+/// - `riders`: How many dragon riders carry each resource
+/// - `fly`: How long a dragon rider spends flying each unit to the dragon depot
+/// - `stewards`: Every steward that supplies the depot
+/// - `supply`: How the stewards pick which stronghold to supply next
+/// - `weights`: How likely each stronghold is to be supplied by the `weighted` policy, keyed by
+///   name
+/// - `timings`: How long each stronghold spends on its operations, keyed by name
+pub struct Scenario {
+    catalog: ResourceCatalog,
+    recipes: RecipeBook,
+    capacities: HashMap<Resource, u32>,
+    riders: HashMap<Resource, u32>,
+    fly: Distribution,
+    stewards: Vec<StewardSettings>,
+    supply: SupplyKind,
+    weights: HashMap<String, u32>,
+    timings: HashMap<String, Timing>
+}
+
+impl Scenario {
+    /// Builds the classic scenario of burnstone, seaplum, and klah, with one stronghold named
+    /// after each resource.
+    pub fn classic() -> Scenario {
+        let catalog = ResourceCatalog::default();
+        let recipes = RecipeBook::classic(&catalog);
+        Scenario::with_defaults(catalog, recipes)
+    }
+
+    /// Loads the scenario from a scenario file.
+    ///
+    /// # Arguments
+    /// * `path`: The path to the scenario file.
+    ///
+    /// # Returns
+    /// An `Err` if the file could not be read or describes an unusable world.
+    pub fn load(path:&Path) -> Result<Scenario, ScenarioError> {
+        let catalog = ResourceCatalog::load(path).map_err(ScenarioError::Catalog)?;
+        let recipes = RecipeBook::load(path, &catalog).map_err(ScenarioError::Recipe)?;
+        let contents = fs::read_to_string(path).map_err(ScenarioError::Io)?;
+        let file:ScenarioFile = toml::from_str(&contents).map_err(ScenarioError::Parse)?;
+        let mut scenario = Scenario::with_defaults(catalog, recipes);
+
+        if let Some(depot) = file.depot {
+            scenario.capacities.values_mut().for_each(|capacity| *capacity = depot.capacity);
+        }
+        if let Some(riders) = file.riders {
+            if let Some(count) = riders.count {
+                scenario.riders.values_mut().for_each(|riders| *riders = count);
+            }
+            if let Some(fly) = riders.fly {
+                validate("riders.fly", &fly)?;
+                scenario.fly = fly;
+            }
+        }
+        if let Some(stewards) = file.steward {
+            scenario.stewards = Scenario::stewards_from(stewards, &scenario.recipes)?;
+        }
+        if let Some(supply) = file.supply {
+            scenario.supply = supply.policy;
+        }
+        let mut timing = Timing::default();
+        if let Some(section) = file.timing {
+            timing.distribute = section.distribute.unwrap_or(timing.distribute);
+            timing.consume = section.consume.unwrap_or(timing.consume);
+        }
+        validate("timing.distribute", &timing.distribute)?;
+        validate("timing.consume", &timing.consume)?;
+        scenario.timings.values_mut().for_each(|stronghold| *stronghold = timing);
+
+        for entry in file.resources {
+            let Some(resource) = scenario.catalog.resources().iter()
+                .find(|resource| resource.name() == entry.name) else {
+                return Err(ScenarioError::Invalid {
+                    setting: format!("resource {}", entry.name),
+                    reason: "there is no resource with that name in the catalog".to_string()
+                });
+            };
+            if let Some(riders) = entry.riders {
+                scenario.riders.insert(resource.clone(), riders);
+            }
+            if let Some(capacity) = entry.capacity {
+                scenario.capacities.insert(resource.clone(), capacity);
+            }
+        }
+        for entry in file.strongholds {
+            let Some(stronghold) = scenario.timings.get_mut(&entry.name) else {
+                return Err(ScenarioError::Invalid {
+                    setting: format!("stronghold {}", entry.name),
+                    reason: "there is no stronghold with that name".to_string()
+                });
+            };
+            if let Some(distribute) = entry.distribute {
+                validate(&format!("stronghold {} distribute", entry.name), &distribute)?;
+                stronghold.distribute = distribute;
+            }
+            if let Some(consume) = entry.consume {
+                validate(&format!("stronghold {} consume", entry.name), &consume)?;
+                stronghold.consume = consume;
+            }
+            if let Some(weight) = entry.weight {
+                scenario.weights.insert(entry.name, weight);
+            }
+        }
+
+        for resource in scenario.catalog.resources() {
+            if scenario.capacities[resource] == 0 {
+                return Err(ScenarioError::Invalid {
+                    setting: format!("resource {} capacity", resource),
+                    reason: "the depot must hold at least one unit".to_string()
+                });
+            }
+            if scenario.riders[resource] == 0 {
+                return Err(ScenarioError::Invalid {
+                    setting: format!("resource {} riders", resource),
+                    reason: "at least one dragon rider must carry it".to_string()
+                });
+            }
+        }
+        for (name, recipe) in scenario.recipes.recipes() {
+            // The steward could never fit such a recipe in the depot, and would wait forever
+            for (resource, count) in recipe.ingredients() {
+                let capacity = scenario.capacities[resource];
+                if *count > capacity {
+                    return Err(ScenarioError::Invalid {
+                        setting: format!("stronghold {} recipe", name),
+                        reason: format!("needs {} units of {}, but the depot holds only {}",
+                                        count, resource, capacity)
+                    });
+                }
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Builds a scenario with the classic depot, dragon riders, steward, supply, and timing.
+    ///
+    /// # Arguments
+    /// * `catalog`: Every resource that exists in the world.
+    /// * `recipes`: The name and recipe of every stronghold in the world.
+    fn with_defaults(catalog:ResourceCatalog, recipes:RecipeBook) -> Scenario {
+        Scenario {
+            capacities: catalog.resources().iter()
+                .map(|resource| (resource.clone(), DEPOT_CAPACITY))
+                .collect(),
+            riders: catalog.resources().iter().map(|resource| (resource.clone(), RIDERS)).collect(),
+            fly: Distribution::Fixed { seconds: 0.0 },
+            stewards: vec![StewardSettings {
+                name: "steward".to_string(),
+                collect: Distribution::Fixed { seconds: 0.0 },
+                strongholds: recipes.recipes().iter().map(|(name, _)| name.clone()).collect()
+            }],
+            supply: SupplyKind::Uniform,
+            weights: recipes.recipes().iter().map(|(name, _)| (name.clone(), 1)).collect(),
+            timings: recipes.recipes().iter()
+                .map(|(name, _)| (name.clone(), Timing::default()))
+                .collect(),
+            catalog,
+            recipes
+        }
+    }
+
+    /// Reads the stewards of the scenario file, which are either a single `[steward]` table or any
+    /// number of `[[steward]]` tables.
+    ///
+    /// # Arguments
+    /// * `stewards`: The `[steward]` table or the `[[steward]]` tables.
+    /// * `recipes`: The name and recipe of every stronghold in the world.
+    ///
+    /// # Returns
+    /// An `Err` if a steward has a setting that can't be used, supplies a stronghold that doesn't
+    /// exist, or shares its name with another steward.
+    fn stewards_from(stewards:toml::Value,
+                     recipes:&RecipeBook) -> Result<Vec<StewardSettings>, ScenarioError> {
+        let entries:Vec<StewardEntry> = match stewards {
+            toml::Value::Array(entries) => entries.into_iter()
+                .map(toml::Value::try_into)
+                .collect::<Result<_, _>>()
+                .map_err(ScenarioError::Parse)?,
+            table => vec![table.try_into().map_err(ScenarioError::Parse)?]
+        };
+        if entries.is_empty() {
+            return Err(ScenarioError::Invalid {
+                setting: "steward".to_string(),
+                reason: "at least one steward must supply the depot".to_string()
+            });
+        }
+        let every:Vec<String> = recipes.recipes().iter().map(|(name, _)| name.clone()).collect();
+        let several = entries.len() > 1;
+        let mut stewards:Vec<StewardSettings> = Vec::new();
+        for (number, entry) in entries.into_iter().enumerate() {
+            // Unnamed stewards are numbered when there are several of them
+            let name = entry.name.unwrap_or_else(|| {
+                if several { (number + 1).to_string() } else { "steward".to_string() }
+            });
+            let setting = format!("steward {}", name);
+            if stewards.iter().any(|steward| steward.name == name) {
+                return Err(ScenarioError::Invalid {
+                    setting,
+                    reason: "another steward has the same name".to_string()
+                });
+            }
+            let collect = entry.collect.unwrap_or(Distribution::Fixed { seconds: 0.0 });
+            validate(&(setting.clone() + " collect"), &collect)?;
+            let strongholds = entry.strongholds.unwrap_or_else(|| every.clone());
+            if strongholds.is_empty() {
+                return Err(ScenarioError::Invalid {
+                    setting: setting + " strongholds",
+                    reason: "the steward must supply at least one stronghold".to_string()
+                });
+            }
+            if let Some(unknown) = strongholds.iter().find(|name| !every.contains(name)) {
+                return Err(ScenarioError::Invalid {
+                    setting: setting + " strongholds",
+                    reason: format!("there is no stronghold named {}", unknown)
+                });
+            }
+            stewards.push(StewardSettings { name, collect, strongholds });
+        }
+        Ok(stewards)
+    }
+
+    /// Sets how many dragon riders carry every resource, overriding the scenario file.
+    ///
+    /// # Arguments
+    /// * `count`: How many dragon riders carry each resource.
+    pub fn set_riders(&mut self, count:u32) {
+        self.riders.values_mut().for_each(|riders| *riders = count);
+    }
+
+    /// Sets how the stewards pick which stronghold to supply next, overriding the scenario file.
+    ///
+    /// # Arguments
+    /// * `supply`: The supply policy of every steward.
+    pub fn set_supply(&mut self, supply:SupplyKind) {
+        self.supply = supply;
+    }
+
+    /// Returns every resource that exists in the world.
+    pub fn catalog(&self) -> &ResourceCatalog {
+        &self.catalog
+    }
+
+    /// Returns the name and recipe of every stronghold in the world.
+    pub fn recipes(&self) -> &RecipeBook {
+        &self.recipes
+    }
+
+    /// Returns how many units of each resource the depot holds.
+    pub fn capacities(&self) -> &HashMap<Resource, u32> {
+        &self.capacities
+    }
+
+    /// Returns how many dragon riders carry a resource.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource that is carried.
+    pub fn riders(&self, resource:&Resource) -> u32 {
+        self.riders.get(resource).copied().unwrap_or(RIDERS)
+    }
+
+    /// Returns how long a dragon rider spends flying each unit to the dragon depot.
+    pub fn fly(&self) -> Distribution {
+        self.fly
+    }
+
+    /// Returns every steward that supplies the depot.
+    pub fn stewards(&self) -> &[StewardSettings] {
+        &self.stewards
+    }
+
+    /// Returns how the stewards pick which stronghold to supply next.
+    pub fn supply(&self) -> SupplyKind {
+        self.supply
+    }
+
+    /// Returns how likely each stronghold is to be supplied by the `weighted` policy, keyed by
+    /// name.
+    pub fn weights(&self) -> &HashMap<String, u32> {
+        &self.weights
+    }
+
+    /// Returns how long a stronghold spends on its operations.
+    ///
+    /// # Arguments
+    /// * `stronghold`: The name of the stronghold.
+    pub fn timing(&self, stronghold:&str) -> Timing {
+        self.timings.get(stronghold).copied().unwrap_or_default()
+    }
+}
+
+/// Checks that a distribution in the scenario file can be used.
+///
+/// # Arguments
+/// * `setting`: Where the distribution was given, for the error message.
+/// * `distribution`: The distribution to check.
+fn validate(setting:&str, distribution:&Distribution) -> Result<(), ScenarioError> {
+    distribution.validate().map_err(|reason| ScenarioError::Invalid {
+        setting: setting.to_string(),
+        reason
+    })
+}
@@ -0,0 +1,82 @@
+//! # Hanging on by a Thread: shutdown.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the shutdown token that is shared by every actor. Once the time limit runs out or
+//! Ctrl-C is pressed, the token is triggered and every `go()` loop stops at its next wait, so the
+//! main thread can join the actors and flush the log before exiting.
+//!
+//! Waits on a `Condvar` are done in slices of `POLL_INTERVAL` so that an actor waiting on a
+//! signal that will never be raised still notices the shutdown.
+//!
+//! ## Dependencies
+//! - `std::sync::{Condvar, Mutex}` for waiting on the token.
+//! - `std::time::Duration` for the length of each slice of a wait.
+//! - `crate::sync::lock` for locking the token.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::sync::lock;
+
+/// How long a wait on a signal lasts before checking the shutdown token again.
+pub const POLL_INTERVAL:Duration = Duration::from_millis(100);
+
+/// Structure that represents the shutdown token shared by every actor
+///
+/// # Fields
+/// - `triggered`: Whether the simulation is shutting down
+/// - `condvar`: Notifies threads waiting on the token that it was triggered
+#[derive(Default)]
+pub struct Shutdown {
+    triggered: Mutex<bool>,
+    condvar: Condvar
+}
+
+impl Shutdown {
+    /// Constructs a new `Shutdown` token that has not been triggered.
+    pub fn new() -> Shutdown {
+        Shutdown::default()
+    }
+
+    /// Triggers the token, telling every actor to stop.
+    pub fn trigger(&self) {
+        let mut triggered = lock(&self.triggered);
+        *triggered = true;
+        self.condvar.notify_all();
+    }
+
+    /// Test to see if the token has been triggered.
+    pub fn is_triggered(&self) -> bool {
+        *lock(&self.triggered)
+    }
+
+    /// Blocks until the token is triggered.
+    pub fn wait(&self) {
+        let guard = lock(&self.triggered);
+        let _guard = self.condvar.wait_while(guard, |triggered| !*triggered)
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+
+    /// Blocks until the token is triggered or `duration` has passed.
+    ///
+    /// # Arguments
+    /// * `duration`: The longest amount of time to wait for.
+    ///
+    /// # Returns
+    /// True if the token was triggered.
+    pub fn wait_timeout(&self, duration:Duration) -> bool {
+        let guard = lock(&self.triggered);
+        let (guard, _) = self.condvar.wait_timeout_while(guard, duration, |triggered| {
+            !*triggered
+        }).unwrap_or_else(PoisonError::into_inner);
+        *guard
+    }
+}
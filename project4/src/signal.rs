@@ -0,0 +1,366 @@
+//! # Hanging on by a Thread: signal.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the signals that the actors notify each other with. A signal is raised by one actor
+//! and waited on by another, and comes in two modes:
+//! - `Binary`: raising a signal that is already raised does nothing, like a flag
+//! - `Counting`: every raise lets one wait through, like a semaphore
+//!
+//! Raising a signal that is already raised logs a warning with the number of raises that are
+//! pending until the next wait is let through. A binary signal loses the wake up, which can starve
+//! whoever was meant to be woken, so the raise is counted as lost. A counting signal keeps the
+//! raise, and counts it as a backlog instead, which shows where work piles up.
+//!
+//! Every signal has a name, and takes care of what each actor used to do by hand around a wait:
+//! - telling the clock which actors are waiting, so virtual time can pass while they do
+//! - giving up the wait when the simulation shuts down
+//! - recording how long each wait took in the statistics, keyed by the name of the signal
+//! - telling the watchdog which signal each actor is waiting on, and when it stops waiting
+//! - no longer counting an actor as waiting if it panics while it waits
+//!
+//! ## Dependencies
+//! - `std::sync::{Condvar, Mutex}` for blocking until the signal is raised.
+//! - `std::time::{Duration, Instant}` for waits that give up after a while.
+//! - `crate::sync::lock` for locking the state of the signal.
+//! - `crate::context::Context` for the clock, shutdown token, log, and statistics of the
+//!   simulation.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{fmt, sync::{Condvar, Mutex, MutexGuard, PoisonError}};
+use std::time::{Duration, Instant};
+
+use crate::{context::Context, event::EventKind, shutdown::POLL_INTERVAL, sync::lock};
+
+/// How raises of a signal add up.
+///
+/// # Variants
+/// - `Binary`: The signal is either raised or not, so raising it twice lets one wait through.
+/// - `Counting`: Every raise lets one wait through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalMode {
+    Binary,
+    Counting
+}
+
+/// The state of a signal, guarded by its lock.
+///
+/// # Fields
+/// - `permits`: How many waits the signal will let through
+/// - `pending`: How many raises have happened since a wait was last let through
+/// - `waiters`: How many actors are waiting on the signal
+/// - `parked`: How many of the waiting actors the clock has been told are waiting
+#[derive(Default)]
+struct SignalState {
+    permits: u32,
+    pending: u32,
+    waiters: u32,
+    parked: u32
+}
+
+/// Structure that represents a signal that one actor raises and another waits on
+///
+/// # Fields
+/// - `name`: The name of the signal, which its waits are recorded under
+/// - `mode`: How raises of the signal add up
+/// - `state`: The permits and waiters of the signal
+/// - `condvar`: Wakes the actors waiting on the signal
+/// - `context`: The clock that is told who is waiting, the shutdown token that cuts waits short,
+///   and the statistics that waits are recorded in
+pub struct Signal {
+    name: String,
+    mode: SignalMode,
+    state: Mutex<SignalState>,
+    condvar: Condvar,
+    context: Context
+}
+
+impl Signal {
+    /// Constructs a new `Signal` that has not been raised.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the signal, which its waits are recorded under.
+    /// * `mode`: How raises of the signal add up.
+    /// * `context`: The clock, shutdown token, and statistics shared by every actor.
+    pub fn new(name:&str, mode:SignalMode, context:&Context) -> Signal {
+        Signal {
+            name: name.to_string(),
+            mode,
+            state: Mutex::new(SignalState::default()),
+            condvar: Condvar::new(),
+            context: context.clone()
+        }
+    }
+
+    /// Returns the name of the signal.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns how raises of the signal add up.
+    pub fn mode(&self) -> SignalMode {
+        self.mode
+    }
+
+    /// Raises the signal, letting one wait through.
+    ///
+    /// Raising a signal that is already raised is logged as a warning. A binary signal lets no
+    /// more waits through, so the raise is counted as a lost wake up, while a counting signal
+    /// keeps the raise and counts it as a backlog.
+    ///
+    /// # Returns
+    /// False if the raise was lost.
+    pub fn raise(&self) -> bool {
+        let mut state = lock(&self.state);
+        state.pending += 1;
+        let pending = state.pending;
+        let raised = state.permits > 0;
+        if raised {
+            let signal = self.name.clone();
+            match self.mode {
+                SignalMode::Binary => {
+                    self.context.log(EventKind::SignalCoalesced { signal, pending });
+                    self.context.record(|stats| stats.record_lost_wakeup(&self.name, pending));
+                },
+                SignalMode::Counting => {
+                    self.context.log(EventKind::SignalBacklogged { signal, pending });
+                    self.context.record(|stats| stats.record_backlog(&self.name, pending));
+                }
+            }
+        }
+        match self.mode {
+            SignalMode::Binary => state.permits = 1,
+            SignalMode::Counting => state.permits += 1
+        }
+        self.settle(&mut state);
+        self.condvar.notify_one();
+        !(raised && self.mode == SignalMode::Binary)
+    }
+
+    /// Blocks until the signal is raised, then lowers it.
+    ///
+    /// # Returns
+    /// True if the signal was raised, false if the simulation is shutting down.
+    pub fn wait(&self) -> bool {
+        self.wait_until(None)
+    }
+
+    /// Blocks until the signal is raised or `timeout` has passed on the wall clock, then lowers
+    /// it if it was raised.
+    ///
+    /// # Arguments
+    /// * `timeout`: The longest amount of time to wait for.
+    ///
+    /// # Returns
+    /// True if the signal was raised, false if the wait timed out or the simulation is shutting
+    /// down.
+    pub fn wait_timeout(&self, timeout:Duration) -> bool {
+        self.wait_until(Some(Instant::now() + timeout))
+    }
+
+    /// Blocks until the signal is raised, the deadline passes, or the simulation shuts down.
+    ///
+    /// # Arguments
+    /// * `deadline`: When to give up waiting, or `None` to wait as long as it takes.
+    fn wait_until(&self, deadline:Option<Instant>) -> bool {
+        let started = self.context.clock.now();
+        let mut waiter = Waiter { signal: self, counted: false };
+        let mut state = waiter.enter();
+        self.context.progress.waiting(&self.name);
+        let raised = loop {
+            if state.permits > 0 {
+                state.permits -= 1;
+                state.pending = match self.mode {
+                    SignalMode::Binary => 0,
+                    SignalMode::Counting => state.pending.saturating_sub(1)
+                };
+                break true;
+            }
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) if !left.is_zero() => left.min(POLL_INTERVAL),
+                    _ => break false
+                },
+                None => POLL_INTERVAL
+            };
+            if self.context.shutdown.is_triggered() {
+                break false;
+            }
+            state = self.condvar.wait_timeout(state, timeout)
+                .unwrap_or_else(PoisonError::into_inner).0;
+        };
+        waiter.leave(&mut state);
+        drop(state);
+        self.context.progress.resumed();
+        if raised {
+            let waited = self.context.clock.now().saturating_sub(started);
+            self.context.record(|stats| stats.record_wait(&self.name, waited));
+        }
+        raised
+    }
+
+    /// Tells the clock about every waiting actor that can't be let through yet, and hands a
+    /// running slot back for every one that can.
+    ///
+    /// # Arguments
+    /// * `state`: The locked state of the signal.
+    fn settle(&self, state:&mut MutexGuard<SignalState>) {
+        let blocked = state.waiters.saturating_sub(state.permits);
+        let key = self as *const Signal as usize;
+        while state.parked < blocked {
+            self.context.clock.park(key);
+            state.parked += 1;
+        }
+        while state.parked > blocked {
+            self.context.clock.unpark(key);
+            state.parked -= 1;
+        }
+    }
+}
+
+/// Structure that represents an actor counted among the waiters of a signal
+///
+/// An actor that panics while it waits would stay counted, and the clock would go on treating it
+/// as waiting on the signal. An actor that is still counted when this is dropped is taken back out
+/// of the waiters instead.
+///
+/// # Fields
+/// - `signal`: The signal being waited on
+/// - `counted`: Whether the actor is counted among the waiters of the signal
+struct Waiter<'a> {
+    signal: &'a Signal,
+    counted: bool
+}
+
+impl<'a> Waiter<'a> {
+    /// Counts the actor among the waiters of the signal.
+    ///
+    /// # Returns
+    /// The locked state of the signal.
+    fn enter(&mut self) -> MutexGuard<'a, SignalState> {
+        let mut state = lock(&self.signal.state);
+        state.waiters += 1;
+        self.counted = true;
+        self.signal.settle(&mut state);
+        state
+    }
+
+    /// Stops counting the actor among the waiters of the signal.
+    ///
+    /// # Arguments
+    /// * `state`: The locked state of the signal.
+    fn leave(&mut self, state:&mut MutexGuard<SignalState>) {
+        state.waiters -= 1;
+        self.counted = false;
+        self.signal.settle(state);
+    }
+}
+
+impl Drop for Waiter<'_> {
+    /// Stops counting an actor that panicked while it waited.
+    fn drop(&mut self) {
+        if self.counted {
+            let mut state = lock(&self.signal.state);
+            self.leave(&mut state);
+        }
+    }
+}
+
+impl fmt::Debug for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signal").field("name", &self.name).field("mode", &self.mode).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{panic::{self, AssertUnwindSafe}, sync::Arc, time::Duration};
+    use serde_json::Value;
+
+    use super::{Signal, SignalMode};
+    use crate::{clock::Clock, context::Context, event::EventKind, logthread::LogHandle};
+    use crate::{shutdown::Shutdown, sync::lock};
+
+    /// A clock that panics whenever an actor has to wait on a signal.
+    struct Panicking;
+
+    impl Clock for Panicking {
+        fn now(&self) -> Duration {
+            Duration::ZERO
+        }
+
+        fn sleep(&self, _duration:Duration) {}
+
+        fn park(&self, _signal:usize) {
+            panic!("the clock could not park the actor");
+        }
+    }
+
+    /// Reads how many raises of a signal were lost, backlogged, and pending at most.
+    ///
+    /// # Arguments
+    /// * `context`: The context whose statistics the raises were recorded in.
+    /// * `signal`: The name of the signal.
+    fn wakeups(context:&Context, signal:&str) -> Value {
+        let stats:Value = serde_json::from_str(&lock(&context.stats).to_json().unwrap()).unwrap();
+        stats["wakeups"][signal].clone()
+    }
+
+    #[test]
+    fn raising_a_counting_signal_twice_before_a_wait_is_a_backlog() {
+        let (context, captured) = Context::capture();
+        let signal = Signal::new("units", SignalMode::Counting, &context);
+        assert!(signal.raise());
+        assert!(signal.raise());
+        assert!(signal.wait());
+        assert!(signal.wait());
+
+        let events = captured.events();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0].kind,
+                         EventKind::SignalBacklogged { signal, pending: 2 } if signal == "units"));
+        let wakeups = wakeups(&context, "units");
+        assert_eq!(wakeups["backlogged"], 1);
+        assert_eq!(wakeups["lost"], 0);
+        assert_eq!(wakeups["most_pending"], 2);
+    }
+
+    #[test]
+    fn raising_a_binary_signal_twice_before_a_wait_loses_a_wakeup() {
+        let (context, captured) = Context::capture();
+        let signal = Signal::new("ready", SignalMode::Binary, &context);
+        assert!(signal.raise());
+        assert!(!signal.raise());
+        assert!(signal.wait());
+        assert!(!signal.wait_timeout(Duration::from_millis(10)));
+
+        let events = captured.events();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0].kind,
+                         EventKind::SignalCoalesced { signal, pending: 2 } if signal == "ready"));
+        let wakeups = wakeups(&context, "ready");
+        assert_eq!(wakeups["lost"], 1);
+        assert_eq!(wakeups["backlogged"], 0);
+    }
+
+    #[test]
+    fn an_actor_that_panics_while_waiting_stops_being_counted() {
+        let (logger, _captured) = LogHandle::capture();
+        let context = Context::new(logger, Arc::new(Panicking), Arc::new(Shutdown::new()));
+        let signal = Signal::new("units", SignalMode::Counting, &context);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| signal.wait())).is_err());
+
+        let state = lock(&signal.state);
+        assert_eq!(state.waiters, 0);
+        assert_eq!(state.parked, 0);
+        drop(state);
+        assert!(signal.raise());
+        assert!(signal.wait());
+    }
+}
@@ -0,0 +1,559 @@
+//! # Hanging on by a Thread: simulation.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the simulation as something that can be embedded:
+//! - `SimulationBuilder` takes a scenario and the settings of a run, and builds the stewards, the
+//!   dragon riders, the strongholds, the depot, and the dragon depot with their signals wired up
+//! - `Simulation` starts a thread for each actor, lets time pass, can be inspected while it runs,
+//!   and stops every thread once the run is over
+//! - `Report` holds what a finished run did
+//!
+//! ## Dependencies
+//! - `std::sync::{Arc, Mutex}` and `crate::signal::Signal` for the signals and shared state of
+//!   the actors.
+//! - `crate::supervisor::Supervisor` for running each actor on its own thread, and restarting the
+//!   ones that panic.
+//! - `rand::{rngs::SmallRng, Rng, SeedableRng}` for seeding the actors, so a run can be replayed.
+//! - `crate::scenario::Scenario` for the world that is simulated.
+//! - `crate::supply::SupplyKind` for how the stewards pick which stronghold to supply next.
+//! - `crate::logger` and `crate::logthread` for writing the log on its own thread.
+//! - `crate::watchdog::Watchdog` for noticing when the simulation stalls.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{collections::HashMap, fmt, io, mem, sync::{Arc, Mutex}};
+use std::time::Duration;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::clock::{Clock, RealClock, VirtualClock};
+use crate::{context::Context, depot::Depot, dragondepot::DragonDepot, event::EventKind};
+use crate::dragonrider::DragonRider;
+use crate::steward::{Steward, StewardMode, StewardSignals, StewardStrategy};
+use crate::stronghold::{Stronghold, StrongholdSignals};
+use crate::logger::Logger;
+use crate::logthread::LogThread;
+use crate::{recipe::Recipe, resource::Resource, scenario::Scenario};
+use crate::{shutdown::Shutdown, signal::{Signal, SignalMode}, stats::{Fairness, Statistics}};
+use crate::supervisor::{Factory, RestartPolicy, Supervision, Supervisor, SupervisorThread};
+use crate::sync::lock;
+use crate::supply::SupplyKind;
+use crate::watchdog::Watchdog;
+
+/// Error returned when a simulation cannot be built or started.
+///
+/// # Variants
+/// - `Spawn`: A thread could not be spawned.
+/// - `LockstepStewards`: Lock step was asked for, but the scenario has this many stewards.
+/// - `AlreadyStarted`: The simulation was started more than once.
+/// - `NotRunning`: Time was let pass before the simulation started, or after `run_until_shutdown`.
+#[derive(Debug)]
+pub enum SimulationError {
+    LockstepStewards(usize),
+    Spawn(io::Error),
+    AlreadyStarted,
+    NotRunning
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::Spawn(e) => write!(f, "could not spawn a thread: {}", e),
+            SimulationError::LockstepStewards(count) => {
+                write!(f, "only a single steward can work in lock step, not {}", count)
+            },
+            SimulationError::AlreadyStarted => write!(f, "the simulation has already started"),
+            SimulationError::NotRunning => {
+                write!(f, "the simulation is not running, so no time can pass on its clock")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+/// Structure that represents the settings of a simulation before it is built
+///
+/// # Fields
+/// - `scenario`: The world that is simulated
+/// - `seed`: The seed of every random number generator, or `None` for a random seed
+/// - `virtual_time`: Whether the simulation runs on a simulated clock
+/// - `logger`: Where events are logged, or `None` to not log them
+/// - `watchdog`: How long no actor may make progress for before the simulation has stalled, or
+///   `None` to not watch for stalls
+/// - `watchdog_abort`: Whether to shut the simulation down once it has stalled
+/// - `restart`: Whether an actor that panicked is started again
+/// - `steward_mode`: How the stewards pace their deliveries, or `None` for lock step with a
+///   single steward and pipelined with several
+pub struct SimulationBuilder {
+    scenario: Scenario,
+    seed: Option<u64>,
+    virtual_time: bool,
+    logger: Option<Logger>,
+    watchdog: Option<Duration>,
+    watchdog_abort: bool,
+    restart: RestartPolicy,
+    steward_mode: Option<StewardMode>
+}
+
+impl SimulationBuilder {
+    /// Constructs a new `SimulationBuilder` for a scenario, with a random seed, the wall clock,
+    /// no log, no watchdog, actors that are never restarted, and a lone steward in lock step.
+    ///
+    /// # Arguments
+    /// * `scenario`: The world that is simulated.
+    pub fn new(scenario:Scenario) -> SimulationBuilder {
+        SimulationBuilder {
+            scenario,
+            seed: None,
+            virtual_time: false,
+            logger: None,
+            watchdog: None,
+            watchdog_abort: false,
+            restart: RestartPolicy::Never,
+            steward_mode: None
+        }
+    }
+
+    /// Seeds every random number generator, so a run can be reproduced.
+    ///
+    /// # Arguments
+    /// * `seed`: The seed of the run.
+    pub fn seed(mut self, seed:u64) -> SimulationBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets how many dragon riders carry every resource, overriding the scenario.
+    ///
+    /// # Arguments
+    /// * `count`: How many dragon riders carry each resource.
+    pub fn riders(mut self, count:u32) -> SimulationBuilder {
+        self.scenario.set_riders(count);
+        self
+    }
+
+    /// Sets how the stewards pick which stronghold to supply next, overriding the scenario.
+    ///
+    /// # Arguments
+    /// * `supply`: The supply policy of every steward.
+    pub fn supply(mut self, supply:SupplyKind) -> SimulationBuilder {
+        self.scenario.set_supply(supply);
+        self
+    }
+
+    /// Sets whether the simulation runs on a simulated clock that jumps straight to the next
+    /// wake up, instead of the wall clock.
+    ///
+    /// # Arguments
+    /// * `virtual_time`: True to run on a simulated clock.
+    pub fn virtual_time(mut self, virtual_time:bool) -> SimulationBuilder {
+        self.virtual_time = virtual_time;
+        self
+    }
+
+    /// Logs every event with a logger, which is run on its own thread.
+    ///
+    /// # Arguments
+    /// * `logger`: The logger that events are written with.
+    pub fn logger(mut self, logger:Logger) -> SimulationBuilder {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Watches for the simulation stalling, which it has once every actor is waiting on a signal
+    /// and none has made progress for `window` on the clock of the simulation.
+    ///
+    /// # Arguments
+    /// * `window`: How long no actor may make progress for before the simulation has stalled.
+    /// * `abort`: Whether to shut the simulation down once it has stalled.
+    pub fn watchdog(mut self, window:Duration, abort:bool) -> SimulationBuilder {
+        self.watchdog = Some(window);
+        self.watchdog_abort = abort;
+        self
+    }
+
+    /// Sets whether an actor whose thread panicked is started again on a new thread.
+    ///
+    /// # Arguments
+    /// * `policy`: Whether, and how many times, an actor is restarted.
+    pub fn restart(mut self, policy:RestartPolicy) -> SimulationBuilder {
+        self.restart = policy;
+        self
+    }
+
+    /// Sets how the stewards pace their deliveries.
+    ///
+    /// # Arguments
+    /// * `mode`: Whether the stewards wait for a stronghold to receive every delivery, or only
+    ///   while the depot has no room. Lock step needs a scenario with a single steward.
+    pub fn steward_mode(mut self, mode:StewardMode) -> SimulationBuilder {
+        self.steward_mode = Some(mode);
+        self
+    }
+
+    /// Builds every actor of the scenario with its signals wired up, without starting them.
+    ///
+    /// # Returns
+    /// The simulation, or an `Err` if the log thread could not be spawned, the recipes of two
+    /// strongholds can't be told apart, or several stewards were asked to work in lock step.
+    pub fn build(self) -> Result<Simulation, SimulationError> {
+        let scenario = self.scenario;
+        let stewards = scenario.stewards().len();
+        let steward_mode = match self.steward_mode {
+            Some(StewardMode::Lockstep) if stewards > 1 => {
+                return Err(SimulationError::LockstepStewards(stewards));
+            },
+            Some(mode) => mode,
+            None if stewards == 1 => StewardMode::Lockstep,
+            None => StewardMode::Pipelined
+        };
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut seeder = SmallRng::seed_from_u64(seed);
+        // Token that tells every actor to stop when time runs out or the run is stopped
+        let shutdown = Arc::new(Shutdown::new());
+        // Clock that every actor tells and passes time with
+        let clock:Arc<dyn Clock> = if self.virtual_time {
+            Arc::new(VirtualClock::new(Arc::clone(&shutdown)))
+        } else {
+            Arc::new(RealClock::new(Arc::clone(&shutdown)))
+        };
+        // Thread that writes status information, so the actors never wait on the log
+        let logger = self.logger.unwrap_or_else(|| Logger::new(Vec::new()));
+        let log_thread = LogThread::spawn(logger).map_err(SimulationError::Spawn)?;
+        // What every actor shares
+        let context = Context::new(log_thread.handle(), Arc::clone(&clock), shutdown);
+
+        // Depot where the stewards store resources to/the dragon riders take from
+        let depot = Arc::new(Mutex::new(Depot::new(scenario.capacities().clone())));
+
+        // Signal for stewards to collect supplies after a stronghold is finished, raised once for
+        // every steward waiting on it
+        let steward_signal = Arc::new(Signal::new(
+            "Steward: stronghold received", SignalMode::Counting, &context
+        ));
+        // Signals from steward that each unit of a resource is supplied in the depot, which every
+        // dragon rider of the resource waits on, so each unit wakes exactly one of them
+        let mut resource_signals = HashMap::new();
+        for resource in scenario.catalog().resources() {
+            let name = resource.name().to_string() + " dragon rider: resource ready";
+            resource_signals.insert(
+                resource.clone(), Arc::new(Signal::new(&name, SignalMode::Counting, &context))
+            );
+        }
+
+        // Mini depot that dragon riders put resources in to know which stronghold to deliever to
+        let mut dragon_depot = DragonDepot::new();
+
+        // Builds every actor again each time it is restarted, drawing a new seed for each
+        let mut supervisor = Supervisor::new(context.clone(), self.restart);
+
+        // List of strongholds, one for each recipe
+        let mut strongholds:Vec<(String, Factory)> = Vec::new();
+        for (name, recipe) in scenario.recipes().recipes() {
+            // Signal from dragon rider's depot that supplies for the stronghold are ready, raised
+            // once for every time its recipe is complete
+            let signal = "Stronghold ".to_string() + name.as_str() + ": resources available";
+            let stronghold_signal = Arc::new(Signal::new(&signal, SignalMode::Counting, &context));
+            context.record(|stats| stats.record_stronghold(name));
+            dragon_depot.register(name.clone(), recipe.clone(), Arc::clone(&stronghold_signal));
+            let (name, recipe, timing) = (name.clone(), recipe.clone(), scenario.timing(name));
+            let (depot, steward_signal) = (Arc::clone(&depot), Arc::clone(&steward_signal));
+            let mut rng = SmallRng::seed_from_u64(seeder.gen());
+            let context = context.clone();
+            strongholds.push(("stronghold-".to_string() + name.as_str(), Box::new(move || {
+                let signals = StrongholdSignals {
+                    received: Arc::clone(&steward_signal),
+                    available: Arc::clone(&stronghold_signal)
+                };
+                Box::new(Stronghold::new(
+                    name.clone(), recipe.clone(), Arc::clone(&depot), signals, timing,
+                    SmallRng::seed_from_u64(rng.gen()), context.clone()
+                ))
+            })));
+        }
+        let dragon_depot = Arc::new(Mutex::new(dragon_depot));
+
+        // Stewards, each supplying the recipes of its own strongholds, picked by its supply policy
+        for settings in scenario.stewards() {
+            let recipes:Vec<(String, Recipe)> = scenario.recipes().recipes().iter()
+                .filter(|(name, _)| settings.strongholds.contains(name))
+                .cloned()
+                .collect();
+            let name = if stewards == 1 {
+                "steward".to_string()
+            } else {
+                "steward-".to_string() + settings.name.as_str()
+            };
+            let settings = settings.clone();
+            let (depot, steward_signal) = (Arc::clone(&depot), Arc::clone(&steward_signal));
+            let resource_signals = resource_signals.clone();
+            let (supply, weights) = (scenario.supply(), scenario.weights().clone());
+            let mut rng = SmallRng::seed_from_u64(seeder.gen());
+            let context = context.clone();
+            supervisor.supervise(name, Box::new(move || {
+                let signals = StewardSignals {
+                    stronghold_received: Arc::clone(&steward_signal),
+                    resource_ready: resource_signals.clone()
+                };
+                let strategy = StewardStrategy {
+                    mode: steward_mode,
+                    supply: supply.policy(&weights, &context.demand)
+                };
+                Box::new(Steward::new(
+                    &settings, recipes.clone(), Arc::clone(&depot), signals, strategy,
+                    SmallRng::seed_from_u64(rng.gen()), context.clone()
+                ))
+            }));
+        }
+        for (name, factory) in strongholds {
+            supervisor.supervise(name, factory);
+        }
+
+        // List of dragon riders, as many as the scenario asks for of each resource, numbered
+        // when there is more than one
+        for resource in scenario.catalog().resources() {
+            let riders = scenario.riders(resource);
+            context.record(|stats| stats.record_fleet(resource.name(), riders));
+            for number in 1..=riders {
+                let mut name = "rider-".to_string() + resource.name();
+                if riders > 1 {
+                    name = name + "-" + number.to_string().as_str();
+                }
+                let resource = resource.clone();
+                let (depot, dragon_depot) = (Arc::clone(&depot), Arc::clone(&dragon_depot));
+                let signal = Arc::clone(&resource_signals[&resource]);
+                let fly = scenario.fly();
+                let mut rng = SmallRng::seed_from_u64(seeder.gen());
+                let context = context.clone();
+                supervisor.supervise(name, Box::new(move || {
+                    Box::new(DragonRider::new(
+                        resource.clone(), Arc::clone(&depot), Arc::clone(&dragon_depot),
+                        Arc::clone(&signal), fly, SmallRng::seed_from_u64(rng.gen()),
+                        context.clone()
+                    ))
+                }));
+            }
+        }
+
+        Ok(Simulation {
+            scenario,
+            seed,
+            steward_mode,
+            context,
+            depot,
+            supervisor: Some(supervisor),
+            supervising: None,
+            log_thread: Some(log_thread),
+            running: false,
+            watchdog_window: self.watchdog,
+            watchdog_abort: self.watchdog_abort,
+            watchdog: None
+        })
+    }
+}
+
+/// Structure that represents what a finished run did
+///
+/// # Fields
+/// - `steward_mode`: How the stewards paced their deliveries
+/// - `elapsed`: The time on the clock when the run finished
+/// - `threads`: The number of actor threads that were joined, counting every restart
+/// - `panicked`: The number of joined threads that had panicked
+/// - `restarts`: The number of actors that were started again after panicking
+/// - `log_complete`: False if the log thread panicked, so the log may be incomplete
+/// - `stalled`: True if the watchdog found that no actor made progress for its whole window
+/// - `stats`: The statistics recorded by the actors
+pub struct Report {
+    pub steward_mode: StewardMode,
+    pub elapsed: Duration,
+    pub threads: usize,
+    pub panicked: usize,
+    pub restarts: usize,
+    pub log_complete: bool,
+    pub stalled: bool,
+    pub stats: Statistics
+}
+
+/// Structure that represents a simulation that has been built
+///
+/// The thread that starts the simulation is counted as running on the clock, so with virtual
+/// time no time passes until it calls `run_for` or `run_until_shutdown`.
+///
+/// # Fields
+/// - `scenario`: The world that is simulated
+/// - `seed`: The seed of every random number generator
+/// - `steward_mode`: How the stewards pace their deliveries
+/// - `context`: What every actor shares
+/// - `depot`: The depot where the steward stores resources
+/// - `supervisor`: The supervisor of every actor, until the simulation is started
+/// - `supervising`: The thread that supervises every actor, once the simulation has started
+/// - `log_thread`: The thread that writes the log, until the simulation is stopped
+/// - `running`: Whether the thread that started the simulation is counted as running
+/// - `watchdog_window`: How long no actor may make progress for before the simulation has
+///   stalled, or `None` to not watch for stalls
+/// - `watchdog_abort`: Whether the watchdog shuts the simulation down once it has stalled
+/// - `watchdog`: The thread that watches for stalls, once the simulation has started
+pub struct Simulation {
+    scenario: Scenario,
+    seed: u64,
+    steward_mode: StewardMode,
+    context: Context,
+    depot: Arc<Mutex<Depot>>,
+    supervisor: Option<Supervisor>,
+    supervising: Option<SupervisorThread>,
+    log_thread: Option<LogThread>,
+    running: bool,
+    watchdog_window: Option<Duration>,
+    watchdog_abort: bool,
+    watchdog: Option<Watchdog>
+}
+
+impl Simulation {
+    /// Starts a thread for every actor, and the supervisor that restarts the ones that panic.
+    ///
+    /// # Returns
+    /// An `Err` if the simulation was already started or a thread could not be spawned. Any
+    /// thread that was spawned keeps running until the simulation is stopped.
+    pub fn start(&mut self) -> Result<(), SimulationError> {
+        let mut supervisor = self.supervisor.take().ok_or(SimulationError::AlreadyStarted)?;
+        self.context.log(EventKind::SimulationStarted { seed: self.seed });
+        for resource in self.scenario.catalog().resources() {
+            self.context.log(EventKind::ResourceListed {
+                resource: resource.name().to_string(),
+                description: resource.description().to_string()
+            });
+        }
+        // Counts the caller as running so simulated time can't pass before every thread starts
+        self.context.clock.register();
+        self.running = true;
+
+        let started = supervisor.start();
+        // Supervises the actors that did start, so they are joined when the simulation stops
+        self.supervising = Some(supervisor.spawn().map_err(SimulationError::Spawn)?);
+        started.map_err(SimulationError::Spawn)?;
+        if let Some(window) = self.watchdog_window {
+            let watchdog = Watchdog::spawn(self.context.clone(), window, self.watchdog_abort)
+                .map_err(SimulationError::Spawn)?;
+            self.watchdog = Some(watchdog);
+        }
+        Ok(())
+    }
+
+    /// Lets the simulation run for an amount of time on its clock, or until it is shut down.
+    ///
+    /// # Arguments
+    /// * `duration`: How long to let the simulation run for.
+    ///
+    /// # Returns
+    /// An `Err` if the simulation was not started, or was already left to run until shutdown.
+    pub fn run_for(&self, duration:Duration) -> Result<(), SimulationError> {
+        if !self.running {
+            return Err(SimulationError::NotRunning);
+        }
+        self.context.clock.sleep(duration);
+        Ok(())
+    }
+
+    /// Lets the simulation run until it is shut down, such as by the token from `shutdown`.
+    pub fn run_until_shutdown(&mut self) {
+        if self.running {
+            self.context.clock.deregister();
+            self.running = false;
+        }
+        self.context.shutdown.wait();
+    }
+
+    /// Returns the token that shuts the simulation down when triggered, such as from a Ctrl-C
+    /// handler.
+    pub fn shutdown(&self) -> Arc<Shutdown> {
+        Arc::clone(&self.context.shutdown)
+    }
+
+    /// Returns the world that is simulated.
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    /// Returns the seed of every random number generator, so the run can be replayed.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns how the stewards pace their deliveries.
+    pub fn steward_mode(&self) -> StewardMode {
+        self.steward_mode
+    }
+
+    /// Returns the time that has passed on the clock of the simulation.
+    pub fn now(&self) -> Duration {
+        self.context.clock.now()
+    }
+
+    /// Returns how many units of a resource are in the depot.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource that is counted.
+    pub fn stock(&self, resource:&Resource) -> u32 {
+        lock(&self.depot).stock(resource)
+    }
+
+    /// Looks at the statistics recorded so far.
+    ///
+    /// # Arguments
+    /// * `inspect`: Called with the statistics while they are locked.
+    pub fn inspect<F, R>(&self, inspect:F) -> R where F: FnOnce(&Statistics) -> R {
+        inspect(&lock(&self.context.stats))
+    }
+
+    /// Returns how fairly the strongholds have been served so far.
+    pub fn fairness(&self) -> Fairness {
+        let now = self.now();
+        self.inspect(|stats| stats.fairness(now))
+    }
+
+    /// Logs how fairly the strongholds have been served so far, and which one went the longest
+    /// without being served.
+    pub fn log_fairness(&self) {
+        let fairness = self.fairness();
+        let (starved, unserved_seconds) = match fairness.most_starved() {
+            Some((name, starved)) => (Some(name.clone()), starved.longest_unserved_seconds),
+            None => (None, 0.0)
+        };
+        self.context.log(EventKind::FairnessMeasured {
+            index: fairness.index,
+            starved,
+            unserved_seconds
+        });
+    }
+
+    /// Tells every actor to stop, waits for the supervisor to join their threads, waits for the
+    /// watchdog and the log thread to finish, and returns what the run did.
+    pub fn stop(mut self) -> Report {
+        self.context.shutdown.trigger();
+        let supervision = self.supervising.take().map(SupervisorThread::join).unwrap_or_default();
+        let Supervision { threads, panicked, restarts } = supervision;
+        let stalled = self.watchdog.take().is_some_and(Watchdog::join);
+        // Writes every event that is still waiting to be logged
+        let log_complete = self.log_thread.take().is_none_or(LogThread::stop);
+        let elapsed = self.context.clock.now();
+        let mut stats = mem::take(&mut *lock(&self.context.stats));
+        stats.finish(elapsed);
+        Report {
+            steward_mode: self.steward_mode,
+            elapsed, threads, panicked, restarts, log_complete, stalled, stats
+        }
+    }
+}
+
+impl Drop for Simulation {
+    /// Tells every actor to stop if the simulation is dropped without being stopped.
+    fn drop(&mut self) {
+        self.context.shutdown.trigger();
+    }
+}
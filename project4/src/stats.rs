@@ -0,0 +1,837 @@
+//! # Hanging on by a Thread: stats.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the statistics that the actors record while the simulation runs:
+//! - how many times the stewards delivered each set of resources
+//! - how many sets each steward delivered, and how long it waited for room in the depot or, in
+//!   lock step, for a stronghold to receive each delivery
+//! - how many dragon riders carry each resource, how many units each of them obtained, and how
+//!   often each found the unit it was woken for already taken by another dragon rider
+//! - how many times each stronghold was served, and how long it spent distributing and consuming
+//! - how long each stronghold waited for its resources, the longest it went without being served,
+//!   and how many times in a row the other strongholds were served meanwhile
+//! - how long each signal was waited on
+//! - how many raises of each binary signal were lost because it was already raised, and how many
+//!   raises of each counting signal piled up behind the ones no actor had taken yet
+//!
+//! Times are measured on the clock of the simulation, so a run with virtual time reports
+//! simulated seconds. Once every thread has been joined, the statistics are printed as a summary
+//! table and can be written out as JSON to compare runs. How fairly the strongholds were served,
+//! including Jain's fairness index of how many times each was served, can also be read as a
+//! `Fairness` while the simulation runs. Two runs of the same scenario can also be printed side by
+//! side as a `Comparison` of their throughput.
+//!
+//! The waits of each stronghold are counted in a `WaitHistogram` of buckets with fixed bounds, so
+//! the statistics take the same room however long the simulation runs, and the percentiles of the
+//! waits are estimated from the buckets.
+//!
+//! ## Dependencies
+//! - `std::collections::BTreeMap` for keeping every table in a stable order.
+//! - `std::time::Duration` for the times that are recorded.
+//! - `serde::{Serialize, Serializer}` and `serde_json` for writing the statistics out as JSON.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+use serde::{Serialize, Serializer};
+
+/// Where the first bucket of a wait histogram ends, in seconds.
+const FIRST_BUCKET_SECONDS:f64 = 0.01;
+
+/// How many buckets of a wait histogram each tenfold of wait time is split into.
+const BUCKETS_PER_DECADE:f64 = 10.0;
+
+/// How many buckets a wait histogram has. The first holds every wait shorter than
+/// `FIRST_BUCKET_SECONDS`, and the last every wait of 100,000 seconds or more.
+const BUCKETS:usize = 72;
+
+/// Structure that represents the amount and length of a kind of timed operation
+///
+/// # Fields
+/// - `count`: How many times the operation happened
+/// - `total_seconds`: How long the operation took altogether
+/// - `max_seconds`: How long the longest operation took
+#[derive(Default, Serialize)]
+pub struct DurationStats {
+    count: u64,
+    total_seconds: f64,
+    max_seconds: f64
+}
+
+impl DurationStats {
+    /// Records one operation.
+    ///
+    /// # Arguments
+    /// * `duration`: How long the operation took.
+    pub fn record(&mut self, duration:Duration) {
+        let seconds = duration.as_secs_f64();
+        self.count += 1;
+        self.total_seconds += seconds;
+        self.max_seconds = self.max_seconds.max(seconds);
+    }
+
+    /// Returns how long an operation took on average, or zero if none happened.
+    pub fn mean_seconds(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_seconds / self.count as f64
+        }
+    }
+}
+
+/// Structure that represents how long a kind of wait took, counted in buckets with fixed bounds
+///
+/// # Fields
+/// - `count`: How many waits there were
+/// - `total_seconds`: How long the waits took altogether
+/// - `min_seconds`: How long the shortest wait took
+/// - `max_seconds`: How long the longest wait took
+/// - `buckets`: How many waits fell in each bucket, where each bucket ends about a quarter later
+///   than the one before it
+#[derive(Serialize)]
+pub struct WaitHistogram {
+    count: u64,
+    total_seconds: f64,
+    min_seconds: f64,
+    max_seconds: f64,
+    #[serde(serialize_with = "serialize_buckets")]
+    buckets: [u64; BUCKETS]
+}
+
+impl Default for WaitHistogram {
+    fn default() -> WaitHistogram {
+        WaitHistogram {
+            count: 0,
+            total_seconds: 0.0,
+            min_seconds: 0.0,
+            max_seconds: 0.0,
+            buckets: [0; BUCKETS]
+        }
+    }
+}
+
+impl WaitHistogram {
+    /// Records one wait.
+    ///
+    /// # Arguments
+    /// * `duration`: How long the wait took.
+    pub fn record(&mut self, duration:Duration) {
+        let seconds = duration.as_secs_f64();
+        self.min_seconds = if self.count == 0 { seconds } else { self.min_seconds.min(seconds) };
+        self.count += 1;
+        self.total_seconds += seconds;
+        self.max_seconds = self.max_seconds.max(seconds);
+        self.buckets[WaitHistogram::bucket(seconds)] += 1;
+    }
+
+    /// Returns how long a wait took on average, or zero if there were none.
+    pub fn mean_seconds(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_seconds / self.count as f64
+        }
+    }
+
+    /// Returns about how long a percentage of the waits took at most, by finding the bucket that
+    /// the wait of that rank fell in and interpolating between where the bucket starts and ends.
+    /// The first bucket holds waits too short to tell apart, so a wait that fell in it is taken to
+    /// be as short as the shortest wait.
+    ///
+    /// # Arguments
+    /// * `percent`: The percentage of waits that took at most the result.
+    pub fn percentile(&self, percent:f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let rank = (percent / 100.0 * self.count as f64).ceil().clamp(1.0, self.count as f64);
+        let mut below = 0.0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            let count = *count as f64;
+            if below + count >= rank {
+                if index == 0 {
+                    return self.min_seconds;
+                }
+                let start = index.checked_sub(1).and_then(WaitHistogram::end).unwrap_or(0.0);
+                let end = WaitHistogram::end(index).unwrap_or(self.max_seconds);
+                let estimate = start + (end - start) * (rank - below) / count;
+                return estimate.clamp(self.min_seconds, self.max_seconds);
+            }
+            below += count;
+        }
+        self.max_seconds
+    }
+
+    /// Returns how many waits took less than a number of seconds.
+    ///
+    /// # Arguments
+    /// * `seconds`: Where one of the buckets ends, such as a whole number of seconds.
+    pub fn count_below(&self, seconds:f64) -> u64 {
+        let end = (BUCKETS_PER_DECADE * (seconds / FIRST_BUCKET_SECONDS).log10()).round() as usize;
+        self.buckets[..(end + 1).min(BUCKETS)].iter().sum()
+    }
+
+    /// Returns the bucket that a wait falls in.
+    ///
+    /// # Arguments
+    /// * `seconds`: How long the wait took.
+    fn bucket(seconds:f64) -> usize {
+        if seconds < FIRST_BUCKET_SECONDS {
+            return 0;
+        }
+        let index = (BUCKETS_PER_DECADE * (seconds / FIRST_BUCKET_SECONDS).log10()).floor();
+        (index as usize + 1).min(BUCKETS - 1)
+    }
+
+    /// Returns where a bucket ends in seconds, or `None` for the last bucket, which never ends.
+    ///
+    /// # Arguments
+    /// * `index`: The position of the bucket.
+    fn end(index:usize) -> Option<f64> {
+        (index < BUCKETS - 1).then(|| {
+            FIRST_BUCKET_SECONDS * 10f64.powf(index as f64 / BUCKETS_PER_DECADE)
+        })
+    }
+}
+
+/// Structure that represents a bucket of a wait histogram as it is written out
+///
+/// # Fields
+/// - `up_to_seconds`: Where the bucket ends, or `None` for the last bucket
+/// - `count`: How many waits fell in the bucket
+#[derive(Serialize)]
+struct Bucket {
+    up_to_seconds: Option<f64>,
+    count: u64
+}
+
+/// Writes out every bucket of a wait histogram that a wait fell in, with where it ends.
+///
+/// # Arguments
+/// * `buckets`: How many waits fell in each bucket.
+/// * `serializer`: What the buckets are written out with.
+fn serialize_buckets<S:Serializer>(buckets:&[u64; BUCKETS], serializer:S)
+    -> Result<S::Ok, S::Error> {
+    let buckets:Vec<Bucket> = buckets.iter().enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(index, count)| Bucket { up_to_seconds: WaitHistogram::end(index), count: *count })
+        .collect();
+    buckets.serialize(serializer)
+}
+
+/// Structure that represents what a dragon rider did
+///
+/// # Fields
+/// - `obtained`: How many units of its resource the dragon rider obtained from the depot
+/// - `found_empty`: How many times the dragon rider found no resource in the depot
+#[derive(Default, Serialize)]
+pub struct RiderStats {
+    obtained: u64,
+    found_empty: u64
+}
+
+impl RiderStats {
+    /// Returns the percentage of times the dragon rider was woken for a unit that another dragon
+    /// rider had already taken, or zero if it was never woken.
+    pub fn contention(&self) -> f64 {
+        let woken = self.obtained + self.found_empty;
+        if woken == 0 {
+            0.0
+        } else {
+            self.found_empty as f64 * 100.0 / woken as f64
+        }
+    }
+}
+
+/// Structure that represents what the dragon riders of one resource did together
+///
+/// # Fields
+/// - `size`: How many dragon riders carry the resource
+/// - `total`: What every dragon rider of the resource did altogether
+/// - `riders`: What each dragon rider did, keyed by thread name
+#[derive(Default, Serialize)]
+pub struct FleetStats {
+    size: u32,
+    total: RiderStats,
+    riders: BTreeMap<String, RiderStats>
+}
+
+/// Structure that represents the raises of a signal that no wait could take right away
+///
+/// # Fields
+/// - `lost`: How many raises of a binary signal were lost because it was already raised
+/// - `backlogged`: How many raises of a counting signal found an earlier raise still waiting to be
+///   taken
+/// - `most_pending`: The most raises of the signal that were pending at once
+#[derive(Default, Serialize)]
+pub struct WakeupStats {
+    lost: u64,
+    backlogged: u64,
+    most_pending: u32
+}
+
+/// Structure that represents what a steward did
+///
+/// # Fields
+/// - `delivered`: How many sets of resources the steward collected for delivery
+/// - `blocked`: How long the steward waited before it could deliver again, for room in the depot
+///   when pipelined, or for a stronghold to receive each delivery in lock step
+#[derive(Default, Serialize)]
+pub struct StewardStats {
+    delivered: u64,
+    blocked: DurationStats
+}
+
+/// Structure that represents what a stronghold did
+///
+/// # Fields
+/// - `served`: How many times the stronghold received its resources
+/// - `distributing`: How long the stronghold spent distributing resources
+/// - `consuming`: How long the stronghold spent consuming resources
+/// - `waits`: How long its waits for its resources took
+/// - `last_served_seconds`: When the stronghold was last served, or zero if it never was
+/// - `longest_unserved_seconds`: The longest the stronghold went without being served, up to when
+///   it was last served
+/// - `streak`: How many times the other strongholds were served since it was last served
+/// - `longest_streak`: The most times the other strongholds were served in a row while it was not
+#[derive(Default, Serialize)]
+pub struct StrongholdStats {
+    served: u64,
+    distributing: DurationStats,
+    consuming: DurationStats,
+    waits: WaitHistogram,
+    #[serde(skip)]
+    last_served_seconds: f64,
+    #[serde(skip)]
+    longest_unserved_seconds: f64,
+    #[serde(skip)]
+    streak: u64,
+    #[serde(skip)]
+    longest_streak: u64
+}
+
+impl StrongholdStats {
+    /// Returns how starved the stronghold has been.
+    ///
+    /// # Arguments
+    /// * `now`: The time on the clock, which ends the time since the stronghold was last served.
+    fn starvation(&self, now:f64) -> Starvation {
+        Starvation {
+            served: self.served,
+            min_wait_seconds: self.waits.min_seconds,
+            mean_wait_seconds: self.waits.mean_seconds(),
+            p50_wait_seconds: self.waits.percentile(50.0),
+            p95_wait_seconds: self.waits.percentile(95.0),
+            max_wait_seconds: self.waits.max_seconds,
+            longest_unserved_seconds: self.longest_unserved_seconds
+                .max(now - self.last_served_seconds),
+            longest_streak: self.longest_streak
+        }
+    }
+}
+
+/// Structure that represents how starved a stronghold has been
+///
+/// # Fields
+/// - `served`: How many times the stronghold received its resources
+/// - `min_wait_seconds`: The shortest wait for its resources
+/// - `mean_wait_seconds`: How long a wait for its resources took on average
+/// - `p50_wait_seconds`: About how long half of its waits took at most
+/// - `p95_wait_seconds`: About how long 95% of its waits took at most
+/// - `max_wait_seconds`: The longest wait for its resources
+/// - `longest_unserved_seconds`: The longest the stronghold went without being served, counting
+///   from the start of the run and up to now
+/// - `longest_streak`: The most times the other strongholds were served in a row while it was not
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Starvation {
+    pub served: u64,
+    pub min_wait_seconds: f64,
+    pub mean_wait_seconds: f64,
+    pub p50_wait_seconds: f64,
+    pub p95_wait_seconds: f64,
+    pub max_wait_seconds: f64,
+    pub longest_unserved_seconds: f64,
+    pub longest_streak: u64
+}
+
+/// Structure that represents how fairly the strongholds have been served
+///
+/// # Fields
+/// - `index`: Jain's fairness index of how many times each stronghold was served, from 1 when
+///   they were all served equally down to 1/n when only one of n strongholds was served, or
+///   `None` if none has been served yet
+/// - `strongholds`: How starved each stronghold has been, keyed by name
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Fairness {
+    pub index: Option<f64>,
+    pub strongholds: BTreeMap<String, Starvation>
+}
+
+impl Fairness {
+    /// Returns the stronghold that went the longest without being served, if there is one.
+    pub fn most_starved(&self) -> Option<(&String, &Starvation)> {
+        self.strongholds.iter().max_by(|(_, a), (_, b)| {
+            a.longest_unserved_seconds.total_cmp(&b.longest_unserved_seconds)
+        })
+    }
+}
+
+impl fmt::Display for Fairness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<30}{:>8}{:>9}{:>9}{:>9}{:>9}{:>9}{:>14}{:>8}", "Starvation", "waits",
+                 "min (s)", "mean (s)", "p50 (s)", "p95 (s)", "max (s)", "unserved (s)", "streak")?;
+        for (name, starved) in &self.strongholds {
+            writeln!(f, "  {:<28}{:>8}{:>9.2}{:>9.2}{:>9.2}{:>9.2}{:>9.2}{:>14.1}{:>8}", name,
+                     starved.served, starved.min_wait_seconds, starved.mean_wait_seconds,
+                     starved.p50_wait_seconds, starved.p95_wait_seconds, starved.max_wait_seconds,
+                     starved.longest_unserved_seconds, starved.longest_streak)?;
+        }
+        match self.index {
+            Some(index) => writeln!(f, "  {:<28}{:>8.3}", "Jain's fairness index", index),
+            None => writeln!(f, "  {:<28}{:>8}", "Jain's fairness index", "-")
+        }
+    }
+}
+
+/// Structure that represents the statistics of a whole run
+///
+/// # Fields
+/// - `elapsed_seconds`: How long the run lasted, set once it has finished
+/// - `deliveries`: How many times the stewards delivered each set of resources, keyed by recipe
+/// - `stewards`: What each steward did, keyed by name
+/// - `riders`: What the dragon riders of each resource did, keyed by resource
+/// - `strongholds`: What each stronghold did, keyed by name
+/// - `waits`: How long each signal was waited on, keyed by signal
+/// - `wakeups`: The raises of each signal that no wait could take right away, keyed by signal
+/// - `fairness`: How fairly the strongholds were served, set once the run has finished
+#[derive(Default, Serialize)]
+pub struct Statistics {
+    elapsed_seconds: f64,
+    deliveries: BTreeMap<String, u64>,
+    stewards: BTreeMap<String, StewardStats>,
+    riders: BTreeMap<String, FleetStats>,
+    strongholds: BTreeMap<String, StrongholdStats>,
+    waits: BTreeMap<String, DurationStats>,
+    wakeups: BTreeMap<String, WakeupStats>,
+    fairness: Option<Fairness>
+}
+
+impl Statistics {
+    /// Constructs a new `Statistics` with nothing recorded.
+    pub fn new() -> Statistics {
+        Statistics::default()
+    }
+
+    /// Records that a steward delivered a set of resources to the depot.
+    ///
+    /// # Arguments
+    /// * `steward`: The name of the steward.
+    /// * `resources`: The recipe whose resources were delivered.
+    pub fn record_delivery(&mut self, steward:&str, resources:&str) {
+        *self.deliveries.entry(resources.to_string()).or_insert(0) += 1;
+        self.stewards.entry(steward.to_string()).or_default().delivered += 1;
+    }
+
+    /// Records how long a steward waited before it could deliver again.
+    ///
+    /// # Arguments
+    /// * `steward`: The name of the steward.
+    /// * `duration`: How long the steward waited for.
+    pub fn record_blocked(&mut self, steward:&str, duration:Duration) {
+        self.stewards.entry(steward.to_string()).or_default().blocked.record(duration);
+    }
+
+    /// Records how many dragon riders carry a resource.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource that is carried.
+    /// * `size`: How many dragon riders carry it.
+    pub fn record_fleet(&mut self, resource:&str, size:u32) {
+        self.riders.entry(resource.to_string()).or_default().size = size;
+    }
+
+    /// Records that a dragon rider obtained a unit of its resource.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource of the dragon rider.
+    /// * `rider`: The name of the thread the dragon rider runs on.
+    pub fn record_obtained(&mut self, resource:&str, rider:&str) {
+        let fleet = self.riders.entry(resource.to_string()).or_default();
+        fleet.total.obtained += 1;
+        fleet.riders.entry(rider.to_string()).or_default().obtained += 1;
+    }
+
+    /// Records that a dragon rider found no resource in the depot.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource of the dragon rider.
+    /// * `rider`: The name of the thread the dragon rider runs on.
+    pub fn record_found_empty(&mut self, resource:&str, rider:&str) {
+        let fleet = self.riders.entry(resource.to_string()).or_default();
+        fleet.total.found_empty += 1;
+        fleet.riders.entry(rider.to_string()).or_default().found_empty += 1;
+    }
+
+    /// Records that a stronghold exists, so it is listed even if it is never served.
+    ///
+    /// # Arguments
+    /// * `stronghold`: The name of the stronghold.
+    pub fn record_stronghold(&mut self, stronghold:&str) {
+        self.strongholds.entry(stronghold.to_string()).or_default();
+    }
+
+    /// Records that a stronghold received its resources, and that every other stronghold went
+    /// without.
+    ///
+    /// # Arguments
+    /// * `stronghold`: The name of the stronghold.
+    /// * `waited`: How long the stronghold waited for its resources.
+    /// * `now`: The time on the clock when the stronghold was served.
+    pub fn record_served(&mut self, stronghold:&str, waited:Duration, now:Duration) {
+        for (name, other) in self.strongholds.iter_mut() {
+            if name != stronghold {
+                other.streak += 1;
+                other.longest_streak = other.longest_streak.max(other.streak);
+            }
+        }
+        let now = now.as_secs_f64();
+        let served = self.strongholds.entry(stronghold.to_string()).or_default();
+        served.served += 1;
+        served.waits.record(waited);
+        served.longest_unserved_seconds =
+            served.longest_unserved_seconds.max(now - served.last_served_seconds);
+        served.last_served_seconds = now;
+        served.streak = 0;
+    }
+
+    /// Records how long a stronghold spent distributing resources.
+    ///
+    /// # Arguments
+    /// * `stronghold`: The name of the stronghold.
+    /// * `duration`: How long the stronghold distributed for.
+    pub fn record_distributing(&mut self, stronghold:&str, duration:Duration) {
+        self.strongholds.entry(stronghold.to_string()).or_default().distributing.record(duration);
+    }
+
+    /// Records how long a stronghold spent consuming resources.
+    ///
+    /// # Arguments
+    /// * `stronghold`: The name of the stronghold.
+    /// * `duration`: How long the stronghold consumed for.
+    pub fn record_consuming(&mut self, stronghold:&str, duration:Duration) {
+        self.strongholds.entry(stronghold.to_string()).or_default().consuming.record(duration);
+    }
+
+    /// Records how long an actor waited on a signal before it was raised.
+    ///
+    /// # Arguments
+    /// * `signal`: The name of the signal that was waited on.
+    /// * `duration`: How long the actor waited for.
+    pub fn record_wait(&mut self, signal:&str, duration:Duration) {
+        self.waits.entry(signal.to_string()).or_default().record(duration);
+    }
+
+    /// Records that a binary signal was raised while it was already raised, losing a wake up.
+    ///
+    /// # Arguments
+    /// * `signal`: The name of the signal that was raised.
+    /// * `pending`: How many raises were pending, counting this one.
+    pub fn record_lost_wakeup(&mut self, signal:&str, pending:u32) {
+        let wakeups = self.wakeups.entry(signal.to_string()).or_default();
+        wakeups.lost += 1;
+        wakeups.most_pending = wakeups.most_pending.max(pending);
+    }
+
+    /// Records that a counting signal was raised while an earlier raise was still waiting to be
+    /// taken.
+    ///
+    /// # Arguments
+    /// * `signal`: The name of the signal that was raised.
+    /// * `pending`: How many raises were pending, counting this one.
+    pub fn record_backlog(&mut self, signal:&str, pending:u32) {
+        let wakeups = self.wakeups.entry(signal.to_string()).or_default();
+        wakeups.backlogged += 1;
+        wakeups.most_pending = wakeups.most_pending.max(pending);
+    }
+
+    /// Records how long the run lasted.
+    ///
+    /// # Arguments
+    /// * `elapsed`: The time on the clock when the run finished.
+    pub fn finish(&mut self, elapsed:Duration) {
+        self.elapsed_seconds = elapsed.as_secs_f64();
+        self.fairness = Some(self.fairness(elapsed));
+    }
+
+    /// Returns how fairly the strongholds have been served so far.
+    ///
+    /// # Arguments
+    /// * `now`: The time on the clock, which ends the time since each stronghold was last served.
+    pub fn fairness(&self, now:Duration) -> Fairness {
+        let served:Vec<f64> = self.strongholds.values().map(|stronghold| stronghold.served as f64)
+            .collect();
+        let squares:f64 = served.iter().map(|count| count * count).sum();
+        let index = (squares > 0.0).then(|| {
+            served.iter().sum::<f64>().powi(2) / (served.len() as f64 * squares)
+        });
+        let strongholds = self.strongholds.iter()
+            .map(|(name, stronghold)| (name.clone(), stronghold.starvation(now.as_secs_f64())))
+            .collect();
+        Fairness { index, strongholds }
+    }
+
+    /// Returns the statistics as pretty printed JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Returns how many times something happened per minute of the run.
+    ///
+    /// # Arguments
+    /// * `count`: How many times it happened.
+    fn per_minute(&self, count:u64) -> f64 {
+        if self.elapsed_seconds > 0.0 {
+            count as f64 * 60.0 / self.elapsed_seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Structure that represents the throughput of two runs of the same scenario, side by side
+///
+/// # Fields
+/// - `baseline`: The name and statistics of the run that is compared against
+/// - `candidate`: The name and statistics of the run that is compared
+pub struct Comparison<'a> {
+    baseline: (String, &'a Statistics),
+    candidate: (String, &'a Statistics)
+}
+
+impl<'a> Comparison<'a> {
+    /// Constructs a new `Comparison` of two runs.
+    ///
+    /// # Arguments
+    /// * `baseline`: The name and statistics of the run that is compared against.
+    /// * `candidate`: The name and statistics of the run that is compared.
+    pub fn new(baseline:(String, &'a Statistics), candidate:(String, &'a Statistics))
+               -> Comparison<'a> {
+        Comparison { baseline, candidate }
+    }
+
+    /// Writes a row of the comparison, with how much the candidate changed from the baseline.
+    ///
+    /// # Arguments
+    /// * `f`: The formatter to write to.
+    /// * `label`: What the row measures.
+    /// * `measure`: Returns what the row measures from the statistics of a run.
+    fn row<F>(&self, f:&mut fmt::Formatter<'_>, label:&str, measure:F) -> fmt::Result
+        where F: Fn(&Statistics) -> f64 {
+        let (baseline, candidate) = (measure(self.baseline.1), measure(self.candidate.1));
+        write!(f, "  {:<44}{:>12.2}{:>12.2}", label, baseline, candidate)?;
+        if baseline > 0.0 {
+            writeln!(f, "{:>12.1}", (candidate - baseline) * 100.0 / baseline)
+        } else {
+            writeln!(f, "{:>12}", "-")
+        }
+    }
+}
+
+impl fmt::Display for Comparison<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<46}{:>12}{:>12}{:>12}", "Throughput", self.baseline.0, self.candidate.0,
+                 "change %")?;
+        self.row(f, "deliveries per min", |stats| {
+            stats.per_minute(stats.deliveries.values().sum())
+        })?;
+        self.row(f, "served per min", |stats| {
+            stats.per_minute(stats.strongholds.values().map(|stronghold| stronghold.served).sum())
+        })?;
+        for name in self.baseline.1.strongholds.keys() {
+            self.row(f, &format!("  {}", name), |stats| {
+                let served = stats.strongholds.get(name).map_or(0, |stronghold| stronghold.served);
+                stats.per_minute(served)
+            })?;
+        }
+        self.row(f, "steward blocked (s)", |stats| {
+            stats.stewards.values().map(|steward| steward.blocked.total_seconds).sum()
+        })?;
+        self.row(f, "fairness index", |stats| {
+            let elapsed = Duration::from_secs_f64(stats.elapsed_seconds);
+            stats.fairness(elapsed).index.unwrap_or_default()
+        })
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<46}{:>10}{:>10}", "Deliveries", "count", "per min")?;
+        let mut total = 0;
+        for (resources, count) in &self.deliveries {
+            total += count;
+            writeln!(f, "  {:<44}{:>10}{:>10.2}", resources, count, self.per_minute(*count))?;
+        }
+        writeln!(f, "  {:<44}{:>10}{:>10.2}", "total", total, self.per_minute(total))?;
+
+        writeln!(f)?;
+        writeln!(f, "{:<46}{:>10}{:>10}{:>14}{:>12}", "Stewards", "delivered", "per min",
+                 "blocked (s)", "mean (s)")?;
+        for (name, steward) in &self.stewards {
+            writeln!(f, "  {:<44}{:>10}{:>10.2}{:>14.1}{:>12.2}", name, steward.delivered,
+                     self.per_minute(steward.delivered), steward.blocked.total_seconds,
+                     steward.blocked.mean_seconds())?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:<46}{:>10}{:>10}{:>10}{:>14}", "Dragon riders", "riders", "obtained",
+                 "empty", "contention %")?;
+        for (resource, fleet) in &self.riders {
+            writeln!(f, "  {:<44}{:>10}{:>10}{:>10}{:>14.1}", resource, fleet.size,
+                     fleet.total.obtained, fleet.total.found_empty, fleet.total.contention())?;
+            if fleet.size <= 1 {
+                continue;
+            }
+            for (name, rider) in &fleet.riders {
+                writeln!(f, "    {:<42}{:>10}{:>10}{:>10}{:>14.1}", name, "", rider.obtained,
+                         rider.found_empty, rider.contention())?;
+            }
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:<46}{:>10}{:>10}{:>16}{:>14}", "Strongholds", "served", "per min",
+                 "distribute (s)", "consume (s)")?;
+        for (name, stronghold) in &self.strongholds {
+            writeln!(f, "  {:<44}{:>10}{:>10.2}{:>16.2}{:>14.2}", name, stronghold.served,
+                     self.per_minute(stronghold.served), stronghold.distributing.mean_seconds(),
+                     stronghold.consuming.mean_seconds())?;
+        }
+
+        writeln!(f)?;
+        write!(f, "{}", self.fairness(Duration::from_secs_f64(self.elapsed_seconds)))?;
+
+        writeln!(f)?;
+        writeln!(f, "{:<30}{:>8}{:>9}{:>9}{:>10}{:>12}{:>11}", "Wait histogram", "waits", "< 1 s",
+                 "1-10 s", "10-100 s", "100-1000 s", ">= 1000 s")?;
+        for (name, stronghold) in &self.strongholds {
+            let waits = &stronghold.waits;
+            let below = [1.0, 10.0, 100.0, 1000.0].map(|seconds| waits.count_below(seconds));
+            writeln!(f, "  {:<28}{:>8}{:>9}{:>9}{:>10}{:>12}{:>11}", name, waits.count, below[0],
+                     below[1] - below[0], below[2] - below[1], below[3] - below[2],
+                     waits.count - below[3])?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:<46}{:>10}{:>12}{:>10}{:>10}", "Waits", "count", "total (s)", "mean (s)",
+                 "max (s)")?;
+        for (signal, wait) in &self.waits {
+            writeln!(f, "  {:<44}{:>10}{:>12.1}{:>10.2}{:>10.2}", signal, wait.count,
+                     wait.total_seconds, wait.mean_seconds(), wait.max_seconds)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:<46}{:>10}{:>12}{:>14}", "Pending wakeups", "lost", "backlogged",
+                 "most pending")?;
+        for (signal, wakeups) in &self.wakeups {
+            writeln!(f, "  {:<44}{:>10}{:>12}{:>14}", signal, wakeups.lost, wakeups.backlogged,
+                     wakeups.most_pending)?;
+        }
+        let lost:u64 = self.wakeups.values().map(|wakeups| wakeups.lost).sum();
+        let backlogged:u64 = self.wakeups.values().map(|wakeups| wakeups.backlogged).sum();
+        writeln!(f, "  {:<44}{:>10}{:>12}", "total", lost, backlogged)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Statistics, WaitHistogram, BUCKETS};
+
+    /// Builds a histogram of waits.
+    ///
+    /// # Arguments
+    /// * `waits`: How long each wait took, in seconds.
+    fn histogram(waits:&[f64]) -> WaitHistogram {
+        let mut histogram = WaitHistogram::default();
+        for seconds in waits {
+            histogram.record(Duration::from_secs_f64(*seconds));
+        }
+        histogram
+    }
+
+    #[test]
+    fn waits_fall_in_the_bucket_that_ends_after_them() {
+        assert_eq!(WaitHistogram::bucket(0.0), 0);
+        assert_eq!(WaitHistogram::bucket(0.009), 0);
+        assert_eq!(WaitHistogram::bucket(0.011), 1);
+        assert_eq!(WaitHistogram::bucket(0.9), 20);
+        assert_eq!(WaitHistogram::bucket(1.1), 21);
+        assert_eq!(WaitHistogram::bucket(1e9), BUCKETS - 1);
+        assert_eq!(WaitHistogram::end(0), Some(0.01));
+        assert!((WaitHistogram::end(20).unwrap() - 1.0).abs() < 1e-9);
+        assert!((WaitHistogram::end(BUCKETS - 2).unwrap() - 1e5).abs() < 1e-6);
+        assert_eq!(WaitHistogram::end(BUCKETS - 1), None);
+        for index in 1..BUCKETS - 1 {
+            let (start, end) = (WaitHistogram::end(index - 1).unwrap(), WaitHistogram::end(index));
+            assert_eq!(WaitHistogram::bucket(start * 1.01), index);
+            assert!(end.unwrap() > start);
+        }
+    }
+
+    #[test]
+    fn count_below_counts_the_waits_shorter_than_a_bound() {
+        let waits = histogram(&[0.0, 0.5, 5.0, 50.0, 500.0, 5000.0]);
+        assert_eq!(waits.count_below(1.0), 2);
+        assert_eq!(waits.count_below(10.0), 3);
+        assert_eq!(waits.count_below(100.0), 4);
+        assert_eq!(waits.count_below(1000.0), 5);
+        assert_eq!(waits.count_below(1e9), 6);
+    }
+
+    #[test]
+    fn percentiles_are_estimated_within_a_bucket() {
+        assert_eq!(histogram(&[]).percentile(50.0), 0.0);
+        assert_eq!(histogram(&[5.0]).percentile(50.0), 5.0);
+
+        let seconds:Vec<f64> = (1..=100).map(f64::from).collect();
+        let waits = histogram(&seconds);
+        let (p50, p95) = (waits.percentile(50.0), waits.percentile(95.0));
+        assert!((45.0..=55.0).contains(&p50), "p50 was {}", p50);
+        assert!((90.0..=100.0).contains(&p95), "p95 was {}", p95);
+        assert_eq!(waits.percentile(100.0), 100.0);
+    }
+
+    #[test]
+    fn percentiles_of_waits_too_short_to_tell_apart_are_the_shortest_wait() {
+        let waits = histogram(&[0.0, 0.0, 0.0, 20.0]);
+        assert_eq!(waits.percentile(50.0), 0.0);
+        assert!(waits.percentile(100.0) > 15.0);
+        assert_eq!(histogram(&[0.0; 10]).percentile(95.0), 0.0);
+    }
+
+    #[test]
+    fn jains_index_is_one_when_every_stronghold_is_served_equally() {
+        let mut stats = Statistics::new();
+        let now = Duration::from_secs(60);
+        for stronghold in ["First", "Second", "Third"] {
+            stats.record_stronghold(stronghold);
+        }
+        assert_eq!(stats.fairness(now).index, None);
+
+        stats.record_served("First", Duration::ZERO, now);
+        let index = stats.fairness(now).index.unwrap();
+        assert!((index - 1.0 / 3.0).abs() < 1e-9, "index was {}", index);
+
+        stats.record_served("Second", Duration::ZERO, now);
+        stats.record_served("Third", Duration::ZERO, now);
+        assert!((stats.fairness(now).index.unwrap() - 1.0).abs() < 1e-9);
+    }
+}
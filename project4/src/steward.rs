@@ -4,18 +4,37 @@
 //! ## Description
 //! Sets up the structure for the Steward which holds references to:
 //! - shared memory of depot
-//! - both resources
+//! - the recipe of every stronghold the steward supplies
+//! - the resources being delivered
+//!
 //! It also holds signals for:
 //! - receiving from a stronghold
-//! - message that firestone is ready to be delivered
-//! - message that seaplum is ready to be delivered
-//! - message that klah is ready to be delivered
-//! The Steward collects, receives, produces, and delivers resources
+//! - messages that each resource is ready to be delivered
+//!
+//! The Steward collects, receives, produces, and delivers resources. A steward works in one of two
+//! modes:
+//! - `Lockstep`: waits for a stronghold to receive every delivery before collecting the next one,
+//!   so at most one delivery is ever in flight, and drops a set the depot has no room for
+//! - `Pipelined`: keeps delivering whenever the depot has room for every unit it collected, and
+//!   only waits for a stronghold to free room while it does not
+//!
+//! Several stewards feeding the same depot always run pipelined, coordinating over its capacity.
+//! Which stronghold's recipe a steward collects next is picked by its supply policy.
 //!
 //! ## Dependencies
-//! - `std::sync::{Arc, Condvar, Mutex, MutexGuard}`: Used for thread-safe sharing and synchronization.
-//! - `rand::{thread_rng, Rng}`: Used to randomly select resources to collect and deliver.
-//! - `crate::{depot::Depot, logger::Logger}`: Dependencies within the project for managing resources and logging.
+//! - `std::sync::{Arc, Mutex, MutexGuard}`: Used for thread-safe sharing of the depot.
+//! - `crate::signal::Signal`: Used for waiting on strongholds and telling dragon riders that their
+//!   resource is ready.
+//! - `std::collections::HashMap`: Used to look up the signal of each resource.
+//! - `rand::rngs::SmallRng`: Used to randomly select resources to collect and deliver.
+//! - `crate::supply::SupplyPolicy`: Used to pick which stronghold's recipe to collect next.
+//! - `crate::timing::Distribution`: Used to draw how long collecting each set of resources takes.
+//! - `crate::scenario::StewardSettings`: The name and collect time of the steward.
+//! - `crate::{context::Context, depot::Depot, recipe, resource}`: Dependencies within the
+//!   project for managing resources, logging, recording statistics, and shutting down.
+//! - `std::{fmt, str::FromStr}`: Used to parse and print the mode of the steward.
+//! - `crate::sync::lock`: Used to lock the depot.
+//! - `crate::supervisor::Runnable`: Used to be run and restarted by the supervisor.
 //!
 //! ## Authors
 //! - Dylan Miller
@@ -24,162 +43,332 @@
 //! ## Instructor
 //! - Dr. William Kreahling
 
-use std::sync::{Arc, Condvar, Mutex, MutexGuard};
-use rand::{thread_rng, Rng};
+use std::{collections::HashMap, fmt, str::FromStr, sync::{Arc, Mutex, MutexGuard}};
+use rand::rngs::SmallRng;
+
+use crate::{context::Context, depot::{Depot, DepotError, Unit}, event::EventKind};
+use crate::{recipe::Recipe, resource::Resource, signal::Signal, timing::Distribution};
+use crate::{scenario::StewardSettings, supervisor::Runnable, supply::SupplyPolicy};
+use crate::sync::lock;
+
+/// How a steward paces its deliveries.
+///
+/// # Variants
+/// - `Lockstep`: The steward waits for a stronghold to receive every delivery before collecting
+///   the next one.
+/// - `Pipelined`: The steward keeps delivering while the depot has room, and only waits for a
+///   stronghold while it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StewardMode {
+    Lockstep,
+    Pipelined
+}
 
-use crate::{depot::Depot, logger::Logger};
+impl FromStr for StewardMode {
+    type Err = String;
+
+    /// Parses `lockstep` or `pipelined`.
+    fn from_str(mode:&str) -> Result<StewardMode, String> {
+        match mode {
+            "lockstep" => Ok(StewardMode::Lockstep),
+            "pipelined" => Ok(StewardMode::Pipelined),
+            mode => Err(format!("expected lockstep or pipelined, not {}", mode))
+        }
+    }
+}
+
+impl fmt::Display for StewardMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StewardMode::Lockstep => write!(f, "lockstep"),
+            StewardMode::Pipelined => write!(f, "pipelined")
+        }
+    }
+}
 
-/// The total number of different resources
-const MAX_RESOURCES:f64 = 3.0;
+/// Structure that represents how a steward paces its deliveries and picks what to collect
+///
+/// # Fields
+/// - `mode`: Whether the steward waits for a stronghold to receive every delivery, or only while
+///   the depot has no room
+/// - `supply`: Picks which stronghold's recipe the steward collects next
+pub struct StewardStrategy {
+    pub mode: StewardMode,
+    pub supply: Box<dyn SupplyPolicy>
+}
 
-/// The types of resources
-pub const RESOURCES:[&'static str; 3] = ["Burnstone", "Seaplum", "Klah"];
+/// Structure that represents the signals a steward waits on and raises
+///
+/// # Fields
+/// - `stronghold_received`: Raised for every waiting steward when a stronghold has received its
+///   resources and freed their room in the depot
+/// - `resource_ready`: Signals that each unit of a resource is in the depot, keyed by resource
+pub struct StewardSignals {
+    pub stronghold_received: Arc<Signal>,
+    pub resource_ready: HashMap<Resource, Arc<Signal>>
+}
 
 /// Represents the steward responsible for managing resource distribution.
 ///
 /// # Fields
+/// - `name`: The name of the steward, which its events are logged under.
+/// - `recipes`: The name and recipe of every stronghold the steward supplies.
 /// - `depot`: Shared memory of the central resource depot.
 /// - `stronghold_received`: Signal from strongholds when resources have been collected.
-/// - `firestone_ready`: Signal that firestone is ready for delivery to the depot.
-/// - `seaplum_ready`: Signal that seaplum is ready for delivery.
-/// - `klah_ready`: Signal that klah is ready for delivery.
-/// - `writer`: Logger for outputting status information.
-/// - `resource1`: First type of resource being handled during the cycle.
-/// - `resource2`: Second type of resource being handled.
+/// - `resource_ready`: Signals that each resource is ready for delivery, keyed by resource.
+/// - `resources`: The resources being handled during the cycle.
+/// - `stronghold`: The name of the stronghold whose recipe is being handled during the cycle.
+/// - `collect`: The distribution that how long collecting each set of resources takes is drawn
+///   from.
+/// - `mode`: Whether the steward waits for a stronghold to receive every delivery, or only waits
+///   while the depot has no room.
+/// - `supply`: Picks which stronghold's recipe the steward collects next.
+/// - `rng`: The random number generator used to pick which resources to collect.
+/// - `context`: Logger for outputting status information, the clock that is told when the steward
+///   waits on and raises signals, and the shutdown token.
 pub struct Steward {
+    name: String,
+    recipes: Vec<(String, Recipe)>,
     depot: Arc<Mutex<Depot>>,
-    stronghold_received: Arc<(Mutex<bool>, Condvar)>,
-    firestone_ready: Arc<(Mutex<bool>, Condvar)>,
-    seaplum_ready: Arc<(Mutex<bool>, Condvar)>,
-    klah_ready: Arc<(Mutex<bool>, Condvar)>,
-    writer: Arc<Mutex<Logger>>,
-    resource1: String,
-    resource2: String
+    stronghold_received: Arc<Signal>,
+    resource_ready: HashMap<Resource, Arc<Signal>>,
+    resources: Vec<Resource>,
+    stronghold: String,
+    collect: Distribution,
+    mode: StewardMode,
+    supply: Box<dyn SupplyPolicy>,
+    rng: SmallRng,
+    context: Context
 }
 
 impl Steward {
     /// Constructs a new `Steward`.
-    /// 
+    ///
     /// # Arguments
+    /// * `settings`: The name of the steward, and the distribution that how long collecting each
+    ///   set of resources takes is drawn from.
+    /// * `recipes`: The name and recipe of every stronghold the steward supplies.
     /// * `depot`: A reference to the depot where the steward will deliever resources.
-    /// * `stronghold`: The signal that tells the stewards that the depot's resources are
-    /// collected by a stronghold.
-    /// * `firestone`: Signal that indicates that burnstone has been supplied to the depot.
-    /// * `seaplum`: Signal that indicates that seaplum has been supplied to the depot.
-    /// * `klah`: Signal that indicates that klah has been supplied to the depot.
-    /// * `writer`: The logger to write status info to.
-    pub fn new(depot:Arc<Mutex<Depot>>, 
-               stronghold:Arc<(Mutex<bool>, Condvar)>,
-               firestone:Arc<(Mutex<bool>, Condvar)>, 
-               seaplum:Arc<(Mutex<bool>, Condvar)>,
-               klah:Arc<(Mutex<bool>, Condvar)>,
-               writer:Arc<Mutex<Logger>>) -> Steward {
+    /// * `signals`: The signal that tells the steward that a stronghold received its resources,
+    ///   and the signals that indicate that each resource has been supplied to the depot.
+    /// * `strategy`: Whether the steward waits for a stronghold to receive every delivery, or only
+    ///   while the depot has no room, and the policy that picks which recipe to collect next.
+    /// * `rng`: The random number generator used to pick which resources to collect.
+    /// * `context`: The logger, clock, and shutdown token shared by every actor.
+    pub fn new(settings:&StewardSettings,
+               recipes:Vec<(String, Recipe)>,
+               depot:Arc<Mutex<Depot>>,
+               signals:StewardSignals,
+               strategy:StewardStrategy,
+               rng:SmallRng,
+               context:Context) -> Steward {
         Steward {
+            name: settings.name.clone(),
+            recipes,
             depot,
-            stronghold_received: stronghold,
-            firestone_ready: firestone,
-            seaplum_ready: seaplum,
-            klah_ready: klah,
-            writer,
-            resource1: String::new(),
-            resource2: String::new()
+            stronghold_received: signals.stronghold_received,
+            resource_ready: signals.resource_ready,
+            resources: Vec::new(),
+            stronghold: String::new(),
+            collect: settings.collect,
+            mode: strategy.mode,
+            supply: strategy.supply,
+            rng,
+            context
         }
     }
-    /// Collects resources randomly to be delivered to the depot.
+
+    /// Collects resources to be delivered to the depot.
+    ///
+    /// A stronghold's recipe is picked by the supply policy and every unit it needs is collected,
+    /// which takes an amount of time drawn from the collect distribution.
     fn collect_resources(&mut self) {
-        let mut rng = thread_rng();
-        let rng1 = (rng.gen::<f64>() * MAX_RESOURCES) as usize;
-        let mut rng2 = (rng.gen::<f64>() * MAX_RESOURCES) as usize;
-        while rng1 == rng2 {
-            rng2 = (rng.gen::<f64>() * MAX_RESOURCES) as usize;
+        let time = self.collect.sample(&mut self.rng);
+        self.context.clock.sleep(time);
+        let chosen = self.supply.choose(&self.recipes, &mut self.rng);
+        let (stronghold, recipe) = &self.recipes[chosen];
+        self.resources = recipe.units();
+        self.stronghold = stronghold.clone();
+        let delivery = recipe.to_string();
+        self.context.record(|stats| stats.record_delivery(&self.name, &delivery));
+    }
+
+    /// Manages the production and delivery of resources to the depot, in lock step.
+    ///
+    /// # Returns
+    /// False if the depot had no room for every unit, in which case nothing was placed and there
+    /// is no delivery for a stronghold to receive.
+    fn produce(&mut self) -> bool {
+        self.collect_resources();
+        let mut depot = lock(&self.depot);
+        if let Err(error) = depot.room_for(&self.resources) {
+            drop(depot);
+            self.write_status(self.delivery_failed(&error));
+            return false;
+        }
+        let delivery = depot.start_delivery();
+        for resource in &self.resources {
+            self.resource_ready(resource, delivery, &mut depot);
         }
-        self.resource1 = String::from(RESOURCES[rng1]);
-        self.resource2 = String::from(RESOURCES[rng2]);
+        // Counted before the depot is unlocked, so the stronghold can't receive the resources
+        // before the steward is waiting for it to
+        depot.add_waiter();
+        drop(depot);
+        self.context.progress.progressed();
+        self.write_status(self.resources_delievered());
+        true
     }
 
-    /// Manages the production and delivery of resources to the depot.
-    fn produce(&mut self) {
+    /// Manages the production and delivery of resources to the depot, pipelined, delivering once
+    /// the depot has room for every unit and waiting for strongholds to free room while it does
+    /// not.
+    ///
+    /// # Returns
+    /// False if the simulation is shutting down.
+    fn supply(&mut self) -> bool {
         self.collect_resources();
-        let lock = &*self.depot;
-        let mut depot = lock.lock().unwrap();
-        self.resource_ready(self.resource1.clone(), &mut depot);
-        self.resource_ready(self.resource2.clone(), &mut depot);
+        let started = self.context.clock.now();
+        let mut depot = lock(&self.depot);
+        if let Err(error) = depot.fits(&self.resources) {
+            // Waits for a delivery of another steward, so a set that never fits can't spin
+            depot.add_waiter();
+            drop(depot);
+            self.write_status(self.delivery_failed(&error));
+            let received = self.stronghold_received.wait();
+            let blocked = self.context.clock.now().saturating_sub(started);
+            self.context.record(|stats| stats.record_blocked(&self.name, blocked));
+            return received;
+        }
+        while !depot.has_room(&self.resources) {
+            depot.add_waiter();
+            drop(depot);
+            self.write_status(self.blocked());
+            if !self.stronghold_received.wait() {
+                return false;
+            }
+            depot = lock(&self.depot);
+        }
+        let delivery = depot.start_delivery();
+        for resource in &self.resources {
+            self.resource_ready(resource, delivery, &mut depot);
+        }
+        drop(depot);
+        let blocked = self.context.clock.now().saturating_sub(started);
+        self.context.record(|stats| stats.record_blocked(&self.name, blocked));
+        self.context.progress.progressed();
         self.write_status(self.resources_delievered());
+        true
+    }
+
+    /// Returns an event detailing the resources delivered.
+    fn resources_delievered(&self) -> EventKind {
+        EventKind::StewardDelivered { steward: self.name.clone(), resources: self.listed() }
     }
 
-    /// Returns a status message detailing the resources delivered.
-    fn resources_delievered(&self) -> String {
-        let mut message = "The steward has delievered resources ".to_string();
-        message = message + self.resource1.clone().as_str() + " and " + 
-                  self.resource2.clone().as_mut_str() + " to the depot";
-        message
+    /// Returns the names of the resources being handled during the cycle.
+    fn listed(&self) -> Vec<String> {
+        self.resources.iter().map(|resource| resource.name().to_string()).collect()
     }
 
-    /// Returns a string message that the Steward is waiting for strongholds to collect supplies.
-    fn waiting(&self) -> String {
-        "The steward is waiting for stronghold to collect supplies".to_string()
+    /// Returns an event that the Steward is waiting for room in the depot.
+    fn blocked(&self) -> EventKind {
+        EventKind::StewardBlocked { steward: self.name.clone(), resources: self.listed() }
     }
 
-    /// Returns a string message that the Steward is ready to collect more resources.
-    fn finished_waiting(&self) -> String {
-        "Steward is now ready to collect resources to give to the depot".to_string()
+    /// Returns an event that the Steward is waiting for strongholds to collect supplies.
+    fn waiting(&self) -> EventKind {
+        EventKind::StewardWaiting { steward: self.name.clone() }
     }
 
-    /// Outputs a status message to the logger.
-    /// 
+    /// Returns an event that the Steward is ready to collect more resources.
+    fn finished_waiting(&self) -> EventKind {
+        EventKind::StewardReady { steward: self.name.clone() }
+    }
+
+    /// Outputs an event to the logger.
+    ///
     /// # Arguments
-    /// * `message`: The message that is being written to the logger.
-    fn write_status(&self, message:String) {
-        let lock = &*self.writer;
-        let mut writer = lock.lock().unwrap();
-        writer.write(message);
-    }
-
-    /// Helper method to signal that a resource is ready in the depot.
-    fn resource_ready(&self, resource:String, depot:&mut MutexGuard<Depot>) {
-        match resource.as_str() {
-            "Burnstone" => {
-                depot.place_burnstone();
-                let (lock2, condvar) = &*self.firestone_ready;
-                let mut ready = lock2.lock().unwrap();
-                *ready = true;
-                condvar.notify_one();
-            },
-            "Seaplum" => {
-                depot.place_seaplum();
-                let (lock2, condvar) = &*self.seaplum_ready;
-                let mut ready = lock2.lock().unwrap();
-                *ready = true;
-                condvar.notify_one();
-            }
-            "Klah" => {
-                depot.place_klah();
-                let (lock2, condvar) = &*self.klah_ready;
-                let mut ready = lock2.lock().unwrap();
-                *ready = true;
-                condvar.notify_one();
-            },
-            _ => { unreachable!() }
+    /// * `event`: The event that is being written to the logger.
+    fn write_status(&self, event:EventKind) {
+        self.context.log(event);
+    }
+
+    /// Returns an event that the depot had no room for a resource.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource that could not be delivered.
+    /// * `error`: The error returned by the depot.
+    fn depot_full(&self, resource:&Resource, error:&DepotError) -> EventKind {
+        EventKind::StewardDeliveryFailed {
+            steward: self.name.clone(),
+            resource: resource.name().to_string(),
+            reason: error.to_string()
         }
     }
 
+    /// Returns an event that the depot could not hold the resources that were collected.
+    ///
+    /// # Arguments
+    /// * `error`: The error returned by the depot.
+    fn delivery_failed(&self, error:&DepotError) -> EventKind {
+        let resource = match error {
+            DepotError::Full { resource, .. } | DepotError::Unknown(resource) => resource.clone()
+        };
+        EventKind::StewardDeliveryFailed {
+            steward: self.name.clone(),
+            resource,
+            reason: error.to_string()
+        }
+    }
+
+    /// Helper method to place a resource in the depot and signal that it is ready.
+    ///
+    /// If the depot has no room for the resource, the failure is logged and no signal is sent.
+    ///
+    /// # Arguments
+    /// * `resource`: The resource being placed.
+    /// * `delivery`: The number of the delivery the unit is part of.
+    /// * `depot`: The locked depot the unit is placed in.
+    fn resource_ready(&self, resource:&Resource, delivery:u64, depot:&mut MutexGuard<Depot>) {
+        let stronghold = self.stronghold.clone();
+        if let Err(error) = depot.place(Unit { resource: resource.clone(), delivery, stronghold }) {
+            self.write_status(self.depot_full(resource, &error));
+            return;
+        }
+        self.resource_ready[resource].raise();
+    }
+
     /// Waits for a signal from strongholds indicating that they have received the resources.
-    fn wait_for_received(&self) {
-        let (lock, condvar) = &*self.stronghold_received;
-        let guard = lock.lock().unwrap();
+    ///
+    /// # Returns
+    /// True if a stronghold received the resources, false if the simulation is shutting down.
+    fn wait_for_received(&self) -> bool {
         self.write_status(self.waiting());
-        let mut guard = condvar.wait_while(guard, |condition| {
-            !*condition
-        }).unwrap();
+        let started = self.context.clock.now();
+        if !self.stronghold_received.wait() {
+            return false;
+        }
+        // Counted as blocked, so lock step and pipelined stewards can be compared
+        let blocked = self.context.clock.now().saturating_sub(started);
+        self.context.record(|stats| stats.record_blocked(&self.name, blocked));
         self.write_status(self.finished_waiting());
-        *guard = false;
+        true
     }
+}
 
-    /// Orchestrates the complete cycle of resource handling from collection to delivery.
-    pub fn go(&mut self) {
-        loop {
-            self.produce();
-            self.wait_for_received();
+impl Runnable for Steward {
+    /// Orchestrates the complete cycle of resource handling from collection to delivery,
+    /// until the simulation shuts down.
+    fn go(&mut self) {
+        while !self.context.shutdown.is_triggered() {
+            let running = match self.mode {
+                // A delivery the depot had no room for never reaches a stronghold to wait for
+                StewardMode::Lockstep => !self.produce() || self.wait_for_received(),
+                StewardMode::Pipelined => self.supply()
+            };
+            if !running {
+                break;
+            }
         }
     }
-}
\ No newline at end of file
+}
@@ -4,18 +4,29 @@
 //! ## Description
 //! Sets up the structure for strongholds that has:
 //! - The name of the stronghold (which contains the resource)
-//! - A signal to tell the steward that supplies have been successfully received
+//! - The recipe of resources the stronghold needs
+//! - A signal to tell the stewards that supplies have been successfully received
+//! - The depot, whose room the supplies took up until they are received
 //! - A signal to receive that the resources that the stronghold is lacking is available
+//!
 //! The strongholds also have the functionality of waiting and receiving resources. Then it
-//! has the ability to distribute and consume resources.
+//! has the ability to distribute and consume resources. A stronghold that panics while it receives
+//! its resources still frees the room they took up in the depot.
 //!
 //! ## Dependencies
 //! This module relies on the Rust standard library's synchronization primitives and threading support:
-//! - `std::sync::{Arc, Condvar, Mutex}` for thread-safe handling of signals and shared state.
-//! - `std::thread` for simulating concurrent operations.
-//! - `std::time::Duration` for managing operation delays.
-//! - `rand::{thread_rng, Rng}` for generating random intervals for resource distribution and consumption.
-//! - `crate::logger::Logger` for logging status and operation messages.
+//! - `std::sync::Arc` and `crate::signal::Signal` for thread-safe handling of signals.
+//! - `std::sync::Mutex` and `crate::depot::Depot` for freeing the room the supplies took up.
+//! - `crate::context::Context` for passing time while distributing and consuming resources and for
+//!   noticing when the simulation shuts down, for recording how long each operation and each wait
+//!   for resources took, and for joining the queue of strongholds that demand driven stewards
+//!   supply.
+//! - `rand::rngs::SmallRng` and `crate::timing::Timing` for drawing how long distributing and
+//!   consuming resources takes.
+//! - `crate::event::EventKind` and `crate::logger::Logger`, through the context, for logging status
+//!   and operation events.
+//! - `crate::sync::lock` for locking the depot.
+//! - `crate::supervisor::Runnable` for being run and restarted by the supervisor.
 //!
 //! ## Authors
 //! - Dylan Miller
@@ -24,27 +35,64 @@
 //! ## Instructor
 //! - Dr. William Kreahling
 
-use std::{sync::{Arc, Condvar, Mutex}, thread, time::Duration};
-use rand::{thread_rng, Rng};
-use crate::logger::Logger;
+use std::sync::{Arc, Mutex};
+use rand::rngs::SmallRng;
+use crate::{context::Context, event::EventKind, recipe::Recipe, signal::Signal, timing::Timing};
+use crate::{depot::Depot, supervisor::Runnable, sync::lock};
 
-/// Minimum time duration for resource handling operations.
-const MIN_SECONDS:f64 = 5.0;
+/// Structure that represents the signals a stronghold raises and waits on
+///
+/// # Fields
+/// - `received`: Raised once for every steward waiting for room in the depot, once the
+///   stronghold has received its resources
+/// - `available`: Raised when the resources the stronghold needs are available
+pub struct StrongholdSignals {
+    pub received: Arc<Signal>,
+    pub available: Arc<Signal>
+}
+
+/// Structure that represents resources a stronghold was signaled for, whose room in the depot is
+/// freed once it is dropped
+///
+/// The room is freed even if the stronghold panics while it receives the resources, so the depot
+/// doesn't lose it for good and the stewards waiting for it are still woken.
+///
+/// # Fields
+/// - `stronghold`: The stronghold receiving the resources
+struct Receipt<'a> {
+    stronghold: &'a Stronghold
+}
+
+impl Drop for Receipt<'_> {
+    /// Frees the room the resources took up in the depot.
+    fn drop(&mut self) {
+        self.stronghold.resources_received();
+    }
+}
 
 /// Represents a stronghold that manages resources within the system.
 ///
 /// # Fields
 /// - `name`: The name of the stronghold, usually related to the specific resource it manages.
-/// - `resources_received`: A signal to notify the steward that resources have been successfully 
-///    received.
-/// - `resources_available`: A signal indicating that resources required by the stronghold are 
-///    available for collection.
-/// - `writer`: A logger for recording status updates and operations.
+/// - `recipe`: The resources that must be delivered before the stronghold can distribute them.
+/// - `depot`: The depot whose room the resources took up until the stronghold received them.
+/// - `resources_received`: A signal to notify the stewards that resources have been successfully
+///   received.
+/// - `resources_available`: A signal indicating that resources required by the stronghold are
+///   available for collection.
+/// - `timing`: The distributions that how long each operation takes is drawn from.
+/// - `rng`: The random number generator used to pick how long each operation takes.
+/// - `context`: The logger for recording status updates, the clock used to pass time while
+///   distributing and consuming, and the shutdown token.
 pub struct Stronghold {
     name: String,
-    resources_received: Arc<(Mutex<bool>, Condvar)>,
-    resources_available: Arc<(Mutex<bool>, Condvar)>,
-    writer: Arc<Mutex<Logger>>
+    recipe: Recipe,
+    depot: Arc<Mutex<Depot>>,
+    resources_received: Arc<Signal>,
+    resources_available: Arc<Signal>,
+    timing: Timing,
+    rng: SmallRng,
+    context: Context
 }
 
 impl Stronghold {
@@ -52,122 +100,149 @@ impl Stronghold {
     /// 
     /// # Arguments
     /// * `name`: The name of the stronghold and the main resource it harvests.
-    /// * `resources_received`: The signal to notify the steward that resources have been 
-    ///    successfully received.
-    /// * `resources_available`: The signal to notify stronghold that its resources are available.
-    /// * `writer`: The logger to write status info to.
+    /// * `recipe`: The resources that must be delivered before the stronghold can distribute them.
+    /// * `depot`: The depot whose room the resources took up until the stronghold received them.
+    /// * `signals`: The signal to notify the stewards that resources have been successfully
+    ///   received, and the signal to notify the stronghold that its resources are available.
+    /// * `timing`: The distributions that how long each operation takes is drawn from.
+    /// * `rng`: The random number generator used to pick how long each operation takes.
+    /// * `context`: The logger, clock, and shutdown token shared by every actor.
     pub fn new(name: String,
-               resources_received: Arc<(Mutex<bool>, Condvar)>,
-               resources_available: Arc<(Mutex<bool>, Condvar)>,
-               writer: Arc<Mutex<Logger>>) -> Stronghold {
+               recipe: Recipe,
+               depot: Arc<Mutex<Depot>>,
+               signals: StrongholdSignals,
+               timing: Timing,
+               rng: SmallRng,
+               context: Context) -> Stronghold {
         Stronghold {
             name,
-            resources_received,
-            resources_available,
-            writer
+            recipe,
+            depot,
+            resources_received: signals.received,
+            resources_available: signals.available,
+            timing,
+            rng,
+            context
         }
     }
 
-    /// Waits for notification that the necessary resources are available at the depot.
-    fn wait_for_resources(&self) {
-        let (lock, condvar) = &*self.resources_available;
-        let guard = lock.lock().unwrap();
+    /// Returns the name of the stronghold.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Waits for notification that the necessary resources are available at the depot, then
+    /// receives them and frees the room they took up in the depot.
+    ///
+    /// # Returns
+    /// True if the resources were received, false if the simulation is shutting down.
+    fn wait_for_resources(&self) -> bool {
         self.write_status(self.waiting());
-        let mut guard = condvar.wait_while(guard, |condition| {
-           !*condition 
-        }).unwrap();
+        self.context.demand.waiting(&self.name);
+        let started = self.context.clock.now();
+        if !self.resources_available.wait() {
+            return false;
+        }
+        let _receipt = Receipt { stronghold: self };
+        self.context.demand.served(&self.name);
+        self.context.progress.progressed();
         self.write_status(self.received());
-        *guard = false;
+        let now = self.context.clock.now();
+        let waited = now.saturating_sub(started);
+        self.context.record(|stats| stats.record_served(&self.name, waited, now));
+        true
     }
 
-    /// Returns a message indicating the stronghold is waiting for resources.
-    fn waiting(&self) -> String {
-        let mut message = "Stronghold ".to_string() + self.name.clone().as_str();
-        message = message + " waiting for its resources";
-        message
+    /// Returns an event indicating the stronghold is waiting for resources.
+    fn waiting(&self) -> EventKind {
+        EventKind::StrongholdWaiting {
+            stronghold: self.name.clone(),
+            recipe: self.recipe.to_string()
+        }
     }
 
-    /// Writes a status message to the logger.
+    /// Writes an event to the logger.
     /// 
     /// # Arguments
-    /// * `message`: The message that is being written to the logger.
-    fn write_status(&self, message:String) {
-        let lock = &*self.writer;
-        let mut writer = lock.lock().unwrap();
-        writer.write(message);
+    /// * `event`: The event that is being written to the logger.
+    fn write_status(&self, event:EventKind) {
+        self.context.log(event);
     }
 
-    /// Returns a message indicating that resources have been received.
-    fn received(&self) -> String {
-        let mut message = "Dragon riders had delievered resources to Stronghold ".to_string();
-        message = message + self.name.clone().as_str();
-        message
+    /// Returns an event indicating that resources have been received.
+    fn received(&self) -> EventKind {
+        EventKind::StrongholdReceived { stronghold: self.name.clone() }
     }
 
-    /// Notifies that resources have been received.
+    /// Notifies that resources have been received, freeing the room they took up in the depot and
+    /// waking every steward that was waiting for it.
     fn resources_received(&self) {
-        let (lock, condvar) = &*self.resources_received;
-        let mut received = lock.lock().unwrap();
-        *received = true;
-        condvar.notify_one();
+        let waiting = lock(&self.depot).release(&self.recipe.units());
+        for _ in 0..waiting {
+            self.resources_received.raise();
+        }
     }
 
     /// Distributes resources within the stronghold.
     /// 
-    /// Waits for a random amount of time (between 5 to 9 seconds).
-    fn distribute_resources(&self) {
-        let mut rng = thread_rng();
-        let time_rng = ((rng.gen::<f64>() * MIN_SECONDS) + MIN_SECONDS) as u64;
-        let time = Duration::from_secs(time_rng);
+    /// Waits for an amount of time drawn from the distribute distribution of its timing.
+    fn distribute_resources(&mut self) {
+        let time = self.timing.distribute.sample(&mut self.rng);
         self.write_status(self.distribute_or_consume(true, false));
-        thread::sleep(time);
-        self.write_status(self.distribute_or_consume(true, true));
+        let started = self.context.clock.now();
+        self.context.clock.sleep(time);
+        if !self.context.shutdown.is_triggered() {
+            self.context.progress.progressed();
+            self.write_status(self.distribute_or_consume(true, true));
+            let took = self.context.clock.now().saturating_sub(started);
+            self.context.record(|stats| stats.record_distributing(&self.name, took));
+        }
     }
 
-    /// Returns a status message for distributing or consuming resources.
+    /// Returns an event for distributing or consuming resources.
     /// 
     /// # Arguments
     /// * `distributing`: Boolean that determines if the stronghold is distributing or consuming
-    ///    with true representing that the stronghold is distributing.
+    ///   with true representing that the stronghold is distributing.
     /// * `finished`: Boolean that determines if the stronghold has started or finished with true
-    ///    representing that the stronghold has finished.
-    fn distribute_or_consume(&self, distributing:bool, finished:bool) -> String {
-        let message = "Stronghold ".to_string() + self.name.clone().as_str();
-        if distributing {
-            if finished {
-                message + " has finished distributing resources"
-            } else {
-                message + " is now distributing resources"
-            }
-        } else {
-            if finished {
-                message + " has finished consuming resources"
-            } else {
-                message + " is now consuming resources"
-            }
+    ///   representing that the stronghold has finished.
+    fn distribute_or_consume(&self, distributing:bool, finished:bool) -> EventKind {
+        let stronghold = self.name.clone();
+        match (distributing, finished) {
+            (true, true) => EventKind::StrongholdDistributed { stronghold },
+            (true, false) => EventKind::StrongholdDistributing { stronghold },
+            (false, true) => EventKind::StrongholdConsumed { stronghold },
+            (false, false) => EventKind::StrongholdConsuming { stronghold }
         }
     }
 
     /// Consumes resources within the stronghold.
     /// 
-    /// Waits for a random amount of time (between 5 to 9 seconds).
-    fn consume_resources(&self) {
-        let mut rng = thread_rng();
-        let time_rng = ((rng.gen::<f64>() * MIN_SECONDS) + MIN_SECONDS) as u64;
-        let time = Duration::from_secs(time_rng);
+    /// Waits for an amount of time drawn from the consume distribution of its timing.
+    fn consume_resources(&mut self) {
+        let time = self.timing.consume.sample(&mut self.rng);
         self.write_status(self.distribute_or_consume(false, false));
-        thread::sleep(time);
-        self.write_status(self.distribute_or_consume(false, true));
+        let started = self.context.clock.now();
+        self.context.clock.sleep(time);
+        if !self.context.shutdown.is_triggered() {
+            self.context.progress.progressed();
+            self.write_status(self.distribute_or_consume(false, true));
+            let took = self.context.clock.now().saturating_sub(started);
+            self.context.record(|stats| stats.record_consuming(&self.name, took));
+        }
     }
+}
 
-    /// Executes the full cycle of resource handling from waiting to consumption.
-    pub fn go(&self) {
-        loop {
-            self.wait_for_resources();
-            self.resources_received();
+impl Runnable for Stronghold {
+    /// Executes the full cycle of resource handling from waiting to consumption,
+    /// until the simulation shuts down.
+    fn go(&mut self) {
+        while !self.context.shutdown.is_triggered() {
+            if !self.wait_for_resources() {
+                break;
+            }
             self.distribute_resources();
             self.consume_resources();
         }
     }
-
-}
\ No newline at end of file
+}
@@ -0,0 +1,319 @@
+//! # Hanging on by a Thread: supervisor.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the supervisor that keeps the actors running when one of them panics:
+//! - `Runnable` is what every actor implements so it can be run on its own thread
+//! - `RestartPolicy` decides whether an actor that panicked is started again
+//! - `Supervisor` starts every actor, then watches their threads from a thread of its own
+//!
+//! The supervisor notices that an actor's thread finished through its `JoinHandle`, logs the
+//! panic, and builds a new actor to start in its place if the restart policy allows it. A thread
+//! that panicked stays counted as running on the clock until the supervisor has dealt with it, so
+//! virtual time can't run ahead while the actor is missing. What the actor was in the middle of is
+//! undone as its thread unwinds, so a unit a dragon rider was carrying goes back to the depot and a
+//! wait on a signal stops being counted, before the new actor starts.
+//!
+//! ## Dependencies
+//! - `std::sync::Arc` for sharing the context with every actor thread.
+//! - `std::thread::{self, JoinHandle}` for running and watching the thread of each actor.
+//! - `std::any::Any` for reading the message of a panic.
+//! - `crate::context::Context` for the clock, shutdown token, log, and progress that every actor
+//!   thread is registered with.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{any::Any, fmt, io, str::FromStr};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::{clock::Clock, context::Context, event::EventKind, shutdown::POLL_INTERVAL};
+use crate::watchdog::Progress;
+
+/// An actor that runs on its own thread until the simulation shuts down.
+pub trait Runnable: Send {
+    /// Runs the actor until the simulation shuts down.
+    fn go(&mut self);
+}
+
+/// Builds a fresh actor, both when the simulation starts and whenever the actor is restarted.
+pub type Factory = Box<dyn FnMut() -> Box<dyn Runnable> + Send>;
+
+/// Whether an actor that panicked is started again.
+///
+/// # Variants
+/// - `Never`: An actor that panicked stays stopped.
+/// - `Always`: An actor is restarted every time it panics.
+/// - `Limited`: An actor is restarted at most this many times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    Always,
+    Limited(u32)
+}
+
+impl RestartPolicy {
+    /// Test to see if an actor may be restarted again.
+    ///
+    /// # Arguments
+    /// * `restarts`: How many times the actor has already been restarted.
+    fn allows(&self, restarts:u32) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::Limited(limit) => restarts < *limit
+        }
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = String;
+
+    /// Parses `never`, `always`, or the most times an actor may be restarted.
+    fn from_str(policy:&str) -> Result<RestartPolicy, String> {
+        match policy {
+            "never" => Ok(RestartPolicy::Never),
+            "always" => Ok(RestartPolicy::Always),
+            limit => limit.parse().map(RestartPolicy::Limited).map_err(|_| {
+                format!("expected never, always, or a number of restarts, not {}", limit)
+            })
+        }
+    }
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestartPolicy::Never => write!(f, "never"),
+            RestartPolicy::Always => write!(f, "always"),
+            RestartPolicy::Limited(limit) => write!(f, "{}", limit)
+        }
+    }
+}
+
+/// Structure that represents what the threads of a finished run did
+///
+/// # Fields
+/// - `threads`: The number of actor threads that were joined, counting every restart
+/// - `panicked`: The number of joined threads that had panicked
+/// - `restarts`: The number of actors that were started again after panicking
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Supervision {
+    pub threads: usize,
+    pub panicked: usize,
+    pub restarts: usize
+}
+
+/// Structure that keeps an actor thread registered with the clock and the watchdog while it runs
+///
+/// # Fields
+/// - `clock`: The clock the thread is counted as running on
+/// - `progress`: The progress the thread is tracked in
+struct Registration {
+    clock: Arc<dyn Clock>,
+    progress: Arc<Progress>
+}
+
+impl Registration {
+    /// Counts a thread that is about to be spawned as running on the clock.
+    ///
+    /// # Arguments
+    /// * `context`: The context whose clock and progress the thread is registered with.
+    fn new(context:&Context) -> Registration {
+        context.clock.register();
+        Registration { clock: Arc::clone(&context.clock), progress: Arc::clone(&context.progress) }
+    }
+}
+
+impl Drop for Registration {
+    /// Stops tracking the thread, and stops counting it as running unless it panicked, in which
+    /// case the supervisor stops counting it once it has dealt with the panic.
+    fn drop(&mut self) {
+        self.progress.finished();
+        if !thread::panicking() {
+            self.clock.deregister();
+        }
+    }
+}
+
+/// Structure that represents an actor watched by the supervisor
+///
+/// # Fields
+/// - `name`: The name of the thread the actor runs on
+/// - `factory`: Builds the actor each time it is started
+/// - `handle`: The thread the actor is running on, if it was started and not yet joined
+/// - `restarts`: How many times the actor has been restarted
+struct Supervised {
+    name: String,
+    factory: Factory,
+    handle: Option<JoinHandle<()>>,
+    restarts: u32
+}
+
+impl Supervised {
+    /// Builds a new actor and starts it on its own thread.
+    ///
+    /// # Arguments
+    /// * `context`: The context the thread is registered with.
+    fn spawn(&mut self, context:&Context) -> io::Result<()> {
+        let mut actor = (self.factory)();
+        let registration = Registration::new(context);
+        let handle = thread::Builder::new().name(self.name.clone()).spawn(move || {
+            registration.progress.started();
+            actor.go();
+            drop(registration);
+        })?;
+        self.handle = Some(handle);
+        Ok(())
+    }
+}
+
+/// Structure that represents the supervisor of every actor, before it is started
+///
+/// # Fields
+/// - `context`: The context every actor thread is registered with and panics are logged to
+/// - `policy`: Whether an actor that panicked is started again
+/// - `actors`: Every actor that is supervised
+pub struct Supervisor {
+    context: Context,
+    policy: RestartPolicy,
+    actors: Vec<Supervised>
+}
+
+impl Supervisor {
+    /// Constructs a new `Supervisor` with no actors.
+    ///
+    /// # Arguments
+    /// * `context`: The context every actor thread is registered with.
+    /// * `policy`: Whether an actor that panicked is started again.
+    pub fn new(context:Context, policy:RestartPolicy) -> Supervisor {
+        Supervisor { context, policy, actors: Vec::new() }
+    }
+
+    /// Adds an actor to be started and supervised.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the thread, which is logged with every event from the actor.
+    /// * `factory`: Builds the actor each time it is started.
+    pub fn supervise(&mut self, name:String, factory:Factory) {
+        self.actors.push(Supervised { name, factory, handle: None, restarts: 0 });
+    }
+
+    /// Starts a thread for every actor, in the order they were added.
+    ///
+    /// # Returns
+    /// An `Err` if a thread could not be spawned, in which case the actors after it are not
+    /// started.
+    pub fn start(&mut self) -> io::Result<()> {
+        for actor in &mut self.actors {
+            actor.spawn(&self.context)?;
+        }
+        Ok(())
+    }
+
+    /// Spawns the thread that watches every actor until the simulation shuts down.
+    ///
+    /// # Returns
+    /// The supervisor thread, or an `Err` if it could not be spawned.
+    pub fn spawn(self) -> io::Result<SupervisorThread> {
+        let thread = thread::Builder::new()
+            .name("supervisor".to_string())
+            .spawn(move || self.run())?;
+        Ok(SupervisorThread { thread })
+    }
+
+    /// Checks on every actor until the simulation shuts down, then joins them all.
+    ///
+    /// # Returns
+    /// What the threads of the actors did.
+    fn run(mut self) -> Supervision {
+        let mut supervision = Supervision::default();
+        while !self.context.shutdown.wait_timeout(POLL_INTERVAL) {
+            self.check(&mut supervision, true);
+        }
+        self.check(&mut supervision, false);
+        supervision
+    }
+
+    /// Joins every actor whose thread has finished, logging and restarting the ones that panicked.
+    ///
+    /// # Arguments
+    /// * `supervision`: What the threads of the actors did so far.
+    /// * `running`: True while the simulation runs. Once it has shut down, every thread is joined
+    ///   and none are restarted.
+    fn check(&mut self, supervision:&mut Supervision, running:bool) {
+        for actor in &mut self.actors {
+            let finished = actor.handle.as_ref().is_some_and(JoinHandle::is_finished);
+            if running && !finished {
+                continue;
+            }
+            let Some(handle) = actor.handle.take() else {
+                continue;
+            };
+            supervision.threads += 1;
+            let Err(payload) = handle.join() else {
+                continue;
+            };
+            supervision.panicked += 1;
+            self.context.log(EventKind::ActorPanicked {
+                crashed: actor.name.clone(),
+                message: panic_message(&*payload)
+            });
+            if running && !self.context.shutdown.is_triggered() {
+                if self.policy.allows(actor.restarts) && actor.spawn(&self.context).is_ok() {
+                    actor.restarts += 1;
+                    supervision.restarts += 1;
+                    self.context.log(EventKind::ActorRestarted {
+                        restarted: actor.name.clone(),
+                        restarts: actor.restarts
+                    });
+                } else {
+                    self.context.log(EventKind::ActorAbandoned {
+                        abandoned: actor.name.clone(),
+                        restarts: actor.restarts
+                    });
+                }
+            }
+            // The panicked thread never stopped counting as running
+            self.context.clock.deregister();
+        }
+    }
+}
+
+/// Returns the message a thread panicked with.
+///
+/// # Arguments
+/// * `payload`: What the thread panicked with.
+fn panic_message(payload:&(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "an unknown panic".to_string()
+    }
+}
+
+/// Structure that represents the thread that supervises every actor
+///
+/// # Fields
+/// - `thread`: The supervisor thread, which returns what the threads of the actors did
+pub struct SupervisorThread {
+    thread: JoinHandle<Supervision>
+}
+
+impl SupervisorThread {
+    /// Waits for the supervisor to join every actor, once the simulation has shut down.
+    ///
+    /// # Returns
+    /// What the threads of the actors did, or nothing if the supervisor itself panicked.
+    pub fn join(self) -> Supervision {
+        self.thread.join().unwrap_or_default()
+    }
+}
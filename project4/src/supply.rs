@@ -0,0 +1,328 @@
+//! # Hanging on by a Thread: supply.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up how a steward picks which stronghold's recipe to collect next:
+//! - `SupplyPolicy` is what every way of picking implements, so the steward can use any of them
+//! - `SupplyKind` names each policy, so it can be picked from the command line or scenario file
+//! - `Demand` keeps track of the order the strongholds started waiting for their resources in
+//!
+//! The policies are:
+//! - `uniform`: every stronghold the steward supplies is equally likely to be picked
+//! - `weighted`: each stronghold is picked in proportion to its weight in the scenario file
+//! - `round-robin`: the strongholds are picked in turn
+//! - `demand`: whatever the stronghold that has been waiting the longest needs is picked
+//!
+//! Strongholds join the demand queue when they start waiting for their resources and leave it when
+//! they receive them. A stronghold that a demand driven steward picks goes to the back of the
+//! queue, as if it had just started waiting, so the next pick serves someone else even before the
+//! resources arrive. When none of the steward's strongholds is waiting, one is picked at random.
+//!
+//! ## Dependencies
+//! - `std::collections::HashMap` for the weight and place in the queue of each stronghold.
+//! - `std::sync::{Arc, Mutex}` and `crate::sync::lock` for sharing the demand queue between
+//!   the strongholds and the stewards.
+//! - `rand::{rngs::SmallRng, Rng}` for the policies that pick at random.
+//! - `serde::Deserialize` for reading the policy from the scenario file.
+//! - `crate::recipe::Recipe` for the recipes that are picked from.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{collections::HashMap, fmt, str::FromStr, sync::{Arc, Mutex}};
+use rand::{rngs::SmallRng, Rng};
+use serde::Deserialize;
+
+use crate::{recipe::Recipe, sync::lock};
+
+/// Picks which stronghold's recipe a steward collects next.
+pub trait SupplyPolicy: Send {
+    /// Picks the recipe to collect next.
+    ///
+    /// # Arguments
+    /// * `recipes`: The name and recipe of every stronghold the steward supplies, never empty.
+    /// * `rng`: The random number generator of the steward.
+    ///
+    /// # Returns
+    /// The index of the picked recipe.
+    fn choose(&mut self, recipes:&[(String, Recipe)], rng:&mut SmallRng) -> usize;
+}
+
+/// The name of each supply policy.
+///
+/// # Variants
+/// - `Uniform`: Every stronghold is equally likely to be picked.
+/// - `Weighted`: Each stronghold is picked in proportion to its weight.
+/// - `RoundRobin`: The strongholds are picked in turn.
+/// - `Demand`: The stronghold that has been waiting the longest is picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SupplyKind {
+    #[default]
+    Uniform,
+    Weighted,
+    RoundRobin,
+    Demand
+}
+
+impl SupplyKind {
+    /// Builds a fresh policy of this kind for a steward.
+    ///
+    /// # Arguments
+    /// * `weights`: The weight of each stronghold, keyed by name, which only `Weighted` uses.
+    /// * `demand`: The order the strongholds started waiting in, which only `Demand` uses.
+    pub fn policy(self,
+                  weights:&HashMap<String, u32>,
+                  demand:&Arc<Demand>) -> Box<dyn SupplyPolicy> {
+        match self {
+            SupplyKind::Uniform => Box::new(Uniform),
+            SupplyKind::Weighted => Box::new(Weighted { weights: weights.clone() }),
+            SupplyKind::RoundRobin => Box::new(RoundRobin { next: 0 }),
+            SupplyKind::Demand => Box::new(DemandDriven { demand: Arc::clone(demand) })
+        }
+    }
+}
+
+impl FromStr for SupplyKind {
+    type Err = String;
+
+    /// Parses `uniform`, `weighted`, `round-robin`, or `demand`.
+    fn from_str(kind:&str) -> Result<SupplyKind, String> {
+        match kind {
+            "uniform" => Ok(SupplyKind::Uniform),
+            "weighted" => Ok(SupplyKind::Weighted),
+            "round-robin" => Ok(SupplyKind::RoundRobin),
+            "demand" => Ok(SupplyKind::Demand),
+            kind => Err(format!("expected uniform, weighted, round-robin, or demand, not {}", kind))
+        }
+    }
+}
+
+impl fmt::Display for SupplyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SupplyKind::Uniform => write!(f, "uniform"),
+            SupplyKind::Weighted => write!(f, "weighted"),
+            SupplyKind::RoundRobin => write!(f, "round-robin"),
+            SupplyKind::Demand => write!(f, "demand")
+        }
+    }
+}
+
+/// Structure that represents a policy that picks every stronghold with the same probability
+struct Uniform;
+
+impl SupplyPolicy for Uniform {
+    fn choose(&mut self, recipes:&[(String, Recipe)], rng:&mut SmallRng) -> usize {
+        rng.gen_range(0..recipes.len())
+    }
+}
+
+/// Structure that represents a policy that picks each stronghold in proportion to its weight
+///
+/// # Fields
+/// - `weights`: The weight of each stronghold, keyed by name, where a missing stronghold weighs 1.
+///   If every stronghold weighs 0, one is picked at random.
+struct Weighted {
+    weights: HashMap<String, u32>
+}
+
+impl SupplyPolicy for Weighted {
+    fn choose(&mut self, recipes:&[(String, Recipe)], rng:&mut SmallRng) -> usize {
+        let weight = |name:&String| u64::from(self.weights.get(name).copied().unwrap_or(1));
+        let total:u64 = recipes.iter().map(|(name, _)| weight(name)).sum();
+        if total == 0 {
+            return rng.gen_range(0..recipes.len());
+        }
+        let mut left = rng.gen_range(0..total);
+        for (index, (name, _)) in recipes.iter().enumerate() {
+            if left < weight(name) {
+                return index;
+            }
+            left -= weight(name);
+        }
+        recipes.len() - 1
+    }
+}
+
+/// Structure that represents a policy that picks the strongholds in turn
+///
+/// # Fields
+/// - `next`: The index of the stronghold that is picked next
+struct RoundRobin {
+    next: usize
+}
+
+impl SupplyPolicy for RoundRobin {
+    fn choose(&mut self, recipes:&[(String, Recipe)], _rng:&mut SmallRng) -> usize {
+        let chosen = self.next % recipes.len();
+        self.next = chosen + 1;
+        chosen
+    }
+}
+
+/// Structure that represents a policy that picks the stronghold that has been waiting the longest
+///
+/// # Fields
+/// - `demand`: The order the strongholds started waiting in
+struct DemandDriven {
+    demand: Arc<Demand>
+}
+
+impl SupplyPolicy for DemandDriven {
+    fn choose(&mut self, recipes:&[(String, Recipe)], rng:&mut SmallRng) -> usize {
+        self.demand.claim(recipes).unwrap_or_else(|| rng.gen_range(0..recipes.len()))
+    }
+}
+
+/// Structure that represents the order the strongholds started waiting for their resources in
+///
+/// # Fields
+/// - `queue`: The next ticket to hand out, and the ticket of each waiting stronghold, keyed by
+///   name, where a lower ticket has been waiting longer
+#[derive(Default)]
+pub struct Demand {
+    queue: Mutex<(u64, HashMap<String, u64>)>
+}
+
+impl Demand {
+    /// Constructs a new `Demand` with no stronghold waiting.
+    pub fn new() -> Demand {
+        Demand::default()
+    }
+
+    /// Records that a stronghold started waiting for its resources, behind every stronghold that
+    /// is already waiting.
+    ///
+    /// # Arguments
+    /// * `stronghold`: The name of the stronghold.
+    pub fn waiting(&self, stronghold:&str) {
+        let mut queue = lock(&self.queue);
+        let (next, waiting) = &mut *queue;
+        waiting.insert(stronghold.to_string(), *next);
+        *next += 1;
+    }
+
+    /// Records that a stronghold received its resources and is no longer waiting.
+    ///
+    /// # Arguments
+    /// * `stronghold`: The name of the stronghold.
+    pub fn served(&self, stronghold:&str) {
+        lock(&self.queue).1.remove(stronghold);
+    }
+
+    /// Picks the stronghold that has been waiting the longest and sends it to the back of the
+    /// queue, as if it had just started waiting.
+    ///
+    /// # Arguments
+    /// * `recipes`: The name and recipe of every stronghold that may be picked.
+    ///
+    /// # Returns
+    /// The index of the picked stronghold, or `None` if none of them is waiting.
+    fn claim(&self, recipes:&[(String, Recipe)]) -> Option<usize> {
+        let mut queue = lock(&self.queue);
+        let (next, waiting) = &mut *queue;
+        let (index, _) = recipes.iter().enumerate()
+            .filter_map(|(index, (name, _))| waiting.get(name).map(|ticket| (index, *ticket)))
+            .min_by_key(|(_, ticket)| *ticket)?;
+        waiting.insert(recipes[index].0.clone(), *next);
+        *next += 1;
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::{Demand, SupplyKind};
+    use crate::{recipe::Recipe, resource::ResourceCatalog};
+
+    /// Returns three strongholds named First, Second, and Third, which all need one unit of Klah.
+    fn recipes() -> Vec<(String, Recipe)> {
+        let klah = Recipe::parse("Klah", &ResourceCatalog::default()).unwrap();
+        ["First", "Second", "Third"].iter().map(|name| (name.to_string(), klah.clone())).collect()
+    }
+
+    /// Counts how many times a policy picks each stronghold.
+    ///
+    /// # Arguments
+    /// * `kind`: The policy that picks.
+    /// * `weights`: The weight of each stronghold.
+    /// * `picks`: How many times the policy picks.
+    fn tally(kind:SupplyKind, weights:&HashMap<String, u32>, picks:usize) -> [usize; 3] {
+        let mut policy = kind.policy(weights, &Arc::new(Demand::new()));
+        let (recipes, mut rng) = (recipes(), SmallRng::seed_from_u64(3));
+        let mut tally = [0; 3];
+        for _ in 0..picks {
+            tally[policy.choose(&recipes, &mut rng)] += 1;
+        }
+        tally
+    }
+
+    #[test]
+    fn the_weighted_policy_picks_in_proportion_to_weight() {
+        let weights = HashMap::from([("First".to_string(), 3), ("Third".to_string(), 0)]);
+        let [first, second, third] = tally(SupplyKind::Weighted, &weights, 4000);
+        assert_eq!(third, 0);
+        assert_eq!(first + second, 4000);
+        // First weighs 3 and Second weighs 1 by default, so about three quarters go to First
+        assert!((2800..=3200).contains(&first), "First was picked {} times", first);
+
+        let nothing = HashMap::from([("First", 0), ("Second", 0), ("Third", 0)]
+            .map(|(name, weight)| (name.to_string(), weight)));
+        assert!(tally(SupplyKind::Weighted, &nothing, 300).iter().all(|count| *count > 0));
+    }
+
+    #[test]
+    fn the_uniform_policy_picks_every_stronghold() {
+        let tally = tally(SupplyKind::Uniform, &HashMap::new(), 3000);
+        assert!(tally.iter().all(|count| (850..=1150).contains(count)), "{:?}", tally);
+    }
+
+    #[test]
+    fn the_round_robin_policy_picks_in_turn() {
+        let mut policy = SupplyKind::RoundRobin.policy(&HashMap::new(), &Arc::new(Demand::new()));
+        let (recipes, mut rng) = (recipes(), SmallRng::seed_from_u64(3));
+        let picks:Vec<usize> = (0..7).map(|_| policy.choose(&recipes, &mut rng)).collect();
+        assert_eq!(picks, [0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(policy.choose(&recipes[..2], &mut rng), 1);
+        assert_eq!(policy.choose(&recipes[..2], &mut rng), 0);
+    }
+
+    #[test]
+    fn the_demand_policy_picks_the_longest_waiting_stronghold() {
+        let demand = Arc::new(Demand::new());
+        let mut policy = SupplyKind::Demand.policy(&HashMap::new(), &demand);
+        let (recipes, mut rng) = (recipes(), SmallRng::seed_from_u64(3));
+        demand.waiting("Elsewhere");
+        demand.waiting("Third");
+        demand.waiting("First");
+        // A picked stronghold goes to the back of the queue until it is served
+        assert_eq!(policy.choose(&recipes, &mut rng), 2);
+        assert_eq!(policy.choose(&recipes, &mut rng), 0);
+        assert_eq!(policy.choose(&recipes, &mut rng), 2);
+        demand.served("First");
+        assert_eq!(policy.choose(&recipes, &mut rng), 2);
+        demand.waiting("Second");
+        assert_eq!(policy.choose(&recipes, &mut rng), 2);
+        assert_eq!(policy.choose(&recipes, &mut rng), 1);
+        demand.served("Second");
+        demand.served("Third");
+        assert!(policy.choose(&recipes, &mut rng) < 3);
+    }
+
+    #[test]
+    fn policies_are_named_the_way_they_are_parsed() {
+        for kind in [SupplyKind::Uniform, SupplyKind::Weighted, SupplyKind::RoundRobin,
+                     SupplyKind::Demand] {
+            assert_eq!(kind.to_string().parse::<SupplyKind>(), Ok(kind));
+        }
+        assert!("fair".parse::<SupplyKind>().is_err());
+    }
+}
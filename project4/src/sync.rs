@@ -0,0 +1,35 @@
+//! # Hanging on by a Thread: sync.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up `lock`, which every shared mutex in the simulation is locked with.
+//!
+//! ## Dependencies
+//! - `std::sync::{Mutex, MutexGuard}` for recovering poisoned locks.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::sync::{Mutex, MutexGuard};
+
+/// Locks a mutex, recovering it if a thread panicked while holding it.
+///
+/// A thread that panics while holding a lock poisons it, and every other actor that locks it
+/// afterwards would panic in turn. The poison is cleared instead, and the caller carries on with
+/// whatever state the panicked thread left behind.
+///
+/// # Arguments
+/// * `mutex`: The mutex to lock.
+///
+/// # Returns
+/// The guard of the mutex.
+pub fn lock<T>(mutex:&Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
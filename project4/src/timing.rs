@@ -0,0 +1,129 @@
+//! # Hanging on by a Thread: timing.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the distributions that the length of each operation is drawn from:
+//! - `fixed`: always takes the same number of seconds
+//! - `uniform`: takes any number of seconds between a minimum and a maximum
+//! - `exponential`: takes a number of seconds drawn from an exponential distribution with a mean
+//!
+//! Distributions are written in the scenario file as inline tables, such as
+//! `{ kind = "uniform", min = 5, max = 9 }`. No operation takes longer than a year, so a length
+//! drawn from an exponential distribution is cut short there.
+//!
+//! ## Dependencies
+//! - `serde::Deserialize` for reading distributions out of the scenario file.
+//! - `rand::{rngs::SmallRng, Rng}` for drawing the length of each operation.
+//! - `std::time::Duration` for the lengths that are drawn.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::time::Duration;
+use rand::{rngs::SmallRng, Rng};
+use serde::Deserialize;
+
+/// Constant that contains the longest an operation can take, in seconds, which is a year
+pub const MAX_SECONDS:f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+/// The distribution that the length of an operation, in seconds, is drawn from.
+///
+/// # Variants
+/// - `Fixed`: Always takes `seconds`.
+/// - `Uniform`: Takes any length between `min` and `max`.
+/// - `Exponential`: Takes a length drawn from an exponential distribution whose mean is `mean`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Distribution {
+    Fixed { seconds: f64 },
+    Uniform { min: f64, max: f64 },
+    Exponential { mean: f64 }
+}
+
+impl Distribution {
+    /// Checks that the distribution only draws lengths that can be waited for.
+    ///
+    /// # Returns
+    /// An `Err` explaining what is wrong if a length is negative, not finite, or longer than
+    /// `MAX_SECONDS`, or if the minimum of a uniform distribution is greater than its maximum.
+    pub fn validate(&self) -> Result<(), String> {
+        let seconds:&[f64] = match self {
+            Distribution::Fixed { seconds } => &[*seconds],
+            Distribution::Uniform { min, max } => &[*min, *max],
+            Distribution::Exponential { mean } => &[*mean]
+        };
+        if seconds.iter().any(|seconds| !(0.0..=MAX_SECONDS).contains(seconds)) {
+            return Err(format!("seconds must be between 0 and {} (a year)", MAX_SECONDS));
+        }
+        if let Distribution::Uniform { min, max } = self {
+            if min > max {
+                return Err(format!("the minimum ({}) is greater than the maximum ({})", min, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws how long an operation takes, which is never longer than `MAX_SECONDS`.
+    ///
+    /// # Arguments
+    /// * `rng`: The random number generator of the actor performing the operation.
+    pub fn sample(&self, rng:&mut SmallRng) -> Duration {
+        let seconds = match *self {
+            Distribution::Fixed { seconds } => seconds,
+            Distribution::Uniform { min, max } => rng.gen_range(min..=max),
+            Distribution::Exponential { mean } => -mean * (1.0 - rng.gen::<f64>()).ln()
+        };
+        Duration::try_from_secs_f64(seconds.clamp(0.0, MAX_SECONDS)).unwrap_or_default()
+    }
+}
+
+/// Structure that represents how long a stronghold spends on each of its operations
+///
+/// # Fields
+/// - `distribute`: How long the stronghold spends distributing resources
+/// - `consume`: How long the stronghold spends consuming resources
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    pub distribute: Distribution,
+    pub consume: Distribution
+}
+
+impl Default for Timing {
+    /// The classic timing of 5 to 9 seconds for each operation.
+    fn default() -> Timing {
+        let classic = Distribution::Uniform { min: 5.0, max: 9.0 };
+        Timing { distribute: classic, consume: classic }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::{Distribution, MAX_SECONDS};
+
+    #[test]
+    fn lengths_longer_than_a_year_are_rejected() {
+        assert!(Distribution::Fixed { seconds: MAX_SECONDS }.validate().is_ok());
+        assert!(Distribution::Fixed { seconds: 1e20 }.validate().is_err());
+        assert!(Distribution::Uniform { min: 0.0, max: f64::MAX }.validate().is_err());
+        assert!(Distribution::Exponential { mean: f64::INFINITY }.validate().is_err());
+        assert!(Distribution::Fixed { seconds: -1.0 }.validate().is_err());
+        assert!(Distribution::Fixed { seconds: f64::NAN }.validate().is_err());
+    }
+
+    #[test]
+    fn a_long_draw_is_cut_short_at_a_year() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let exponential = Distribution::Exponential { mean: MAX_SECONDS };
+        for _ in 0..1000 {
+            assert!(exponential.sample(&mut rng).as_secs_f64() <= MAX_SECONDS);
+        }
+        let unchecked = Distribution::Fixed { seconds: 1e20 };
+        assert_eq!(unchecked.sample(&mut rng).as_secs_f64(), MAX_SECONDS);
+    }
+}
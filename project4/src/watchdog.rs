@@ -0,0 +1,220 @@
+//! # Hanging on by a Thread: watchdog.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Sets up the watchdog that notices when the simulation stalls:
+//! - `Progress` tracks when each actor last made progress, and which signal it is waiting on
+//! - `Watchdog` runs on its own thread and checks the progress of every actor
+//!
+//! An actor is tracked from when its thread starts until it finishes, and makes progress whenever
+//! it finishes a step of its work: a steward delivering a set, a dragon rider placing a unit in the
+//! dragon riders' depot, or a stronghold receiving, distributing, or consuming its resources.
+//! Logging an event or being woken by a signal is not progress, so an actor that keeps waking up
+//! and going back to waiting can't hide a stall. The simulation has stalled once every actor is
+//! waiting on a signal and none has made progress for longer than the window of the watchdog. An
+//! actor that is sleeping through a step of its work is busy, however long the step takes. The
+//! watchdog then logs a warning and which signal each actor is waiting on, and can shut the
+//! simulation down so the program exits with an error instead of sitting silent until the time
+//! runs out.
+//!
+//! The window is measured on the clock of the simulation, and the watchdog sleeps on that clock
+//! between checks, so with virtual time it wakes up before time can jump past a stall.
+//!
+//! ## Dependencies
+//! - `std::collections::BTreeMap` for listing the actors in a stable order.
+//! - `std::sync::{Arc, Mutex}` for sharing the progress of every actor with the watchdog.
+//! - `crate::clock::Clock` for measuring how long each actor has made no progress.
+//! - `crate::sync::lock` for locking the progress of the actors.
+//! - `std::thread::{self, JoinHandle}` for running the watchdog on its own thread.
+//! - `std::time::Duration` for the window of the watchdog.
+//! - `crate::context::Context` for logging what the watchdog found and shutting down.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{collections::BTreeMap, io, sync::{Arc, Mutex}, time::Duration};
+use std::thread::{self, JoinHandle};
+
+use crate::{clock::Clock, context::Context, event::{thread_name, EventKind}, sync::lock};
+
+/// The longest time the watchdog sleeps between checks, on the clock of the simulation.
+const CHECK_INTERVAL:Duration = Duration::from_secs(1);
+
+/// Structure that represents the progress of one actor
+///
+/// # Fields
+/// - `last_progress`: When the actor last made progress, on the clock of the simulation
+/// - `waiting_on`: The name of the signal the actor is waiting on, if any
+struct ActorProgress {
+    last_progress: Duration,
+    waiting_on: Option<String>
+}
+
+/// Structure that represents how long one actor has made no progress
+///
+/// # Fields
+/// - `thread`: The name of the thread the actor runs on
+/// - `waiting_on`: The name of the signal the actor is waiting on, if any
+/// - `idle`: How long the actor has made no progress
+struct IdleActor {
+    thread: String,
+    waiting_on: Option<String>,
+    idle: Duration
+}
+
+/// Structure that represents the progress of every running actor, keyed by thread name
+///
+/// # Fields
+/// - `clock`: The clock of the simulation, which progress is timed on
+/// - `actors`: The progress of each actor
+pub struct Progress {
+    clock: Arc<dyn Clock>,
+    actors: Mutex<BTreeMap<String, ActorProgress>>
+}
+
+impl Progress {
+    /// Constructs a new `Progress` with no actors.
+    ///
+    /// # Arguments
+    /// * `clock`: The clock of the simulation, which progress is timed on.
+    pub fn new(clock:Arc<dyn Clock>) -> Progress {
+        Progress { clock, actors: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Starts tracking the actor on the current thread, as having just made progress.
+    pub fn started(&self) {
+        let progress = ActorProgress { last_progress: self.clock.now(), waiting_on: None };
+        lock(&self.actors).insert(thread_name(), progress);
+    }
+
+    /// Records that the actor on the current thread finished a step of its work. Threads that are
+    /// not tracked, such as the main thread and the watchdog itself, are ignored.
+    pub fn progressed(&self) {
+        let now = self.clock.now();
+        if let Some(actor) = lock(&self.actors).get_mut(&thread_name()) {
+            actor.last_progress = now;
+        }
+    }
+
+    /// Records that the actor on the current thread is waiting on a signal.
+    ///
+    /// # Arguments
+    /// * `signal`: The name of the signal being waited on.
+    pub fn waiting(&self, signal:&str) {
+        if let Some(actor) = lock(&self.actors).get_mut(&thread_name()) {
+            actor.waiting_on = Some(signal.to_string());
+        }
+    }
+
+    /// Records that the actor on the current thread stopped waiting on a signal.
+    pub fn resumed(&self) {
+        if let Some(actor) = lock(&self.actors).get_mut(&thread_name()) {
+            actor.waiting_on = None;
+        }
+    }
+
+    /// Stops tracking the actor on the current thread, once it will never run again.
+    pub fn finished(&self) {
+        lock(&self.actors).remove(&thread_name());
+    }
+
+    /// Returns how long each actor has made no progress and which signal it is waiting on.
+    fn idle(&self) -> Vec<IdleActor> {
+        let now = self.clock.now();
+        lock(&self.actors).iter()
+            .map(|(thread, actor)| IdleActor {
+                thread: thread.clone(),
+                waiting_on: actor.waiting_on.clone(),
+                idle: now.saturating_sub(actor.last_progress)
+            })
+            .collect()
+    }
+}
+
+/// Structure that represents the thread that watches for a stalled simulation
+///
+/// # Fields
+/// - `thread`: The watchdog thread, which returns whether the simulation stalled
+pub struct Watchdog {
+    thread: JoinHandle<bool>
+}
+
+impl Watchdog {
+    /// Spawns the watchdog thread, which runs until the simulation shuts down. The thread is
+    /// counted as running on the clock, like an actor.
+    ///
+    /// # Arguments
+    /// * `context`: The context whose actors are watched, and whose log and shutdown token are
+    ///   used when the simulation stalls.
+    /// * `window`: How long no actor may make progress for before the simulation has stalled.
+    /// * `abort`: Whether to shut the simulation down once it has stalled.
+    ///
+    /// # Returns
+    /// The watchdog, or an `Err` if the thread could not be spawned.
+    pub fn spawn(context:Context, window:Duration, abort:bool) -> io::Result<Watchdog> {
+        let clock = Arc::clone(&context.clock);
+        clock.register();
+        let thread = thread::Builder::new()
+            .name("watchdog".to_string())
+            .spawn(move || Watchdog::run(context, window, abort))
+            .inspect_err(|_| clock.deregister())?;
+        Ok(Watchdog { thread })
+    }
+
+    /// Checks the progress of every actor until the simulation shuts down, reporting each stall
+    /// once.
+    ///
+    /// # Arguments
+    /// * `context`: The context whose actors are watched.
+    /// * `window`: How long no actor may make progress for before the simulation has stalled.
+    /// * `abort`: Whether to shut the simulation down once it has stalled.
+    ///
+    /// # Returns
+    /// True if the simulation stalled.
+    fn run(context:Context, window:Duration, abort:bool) -> bool {
+        let mut stalled = false;
+        let mut reported = false;
+        while !context.shutdown.is_triggered() {
+            context.clock.sleep(window.min(CHECK_INTERVAL));
+            let idle = context.progress.idle();
+            let Some(least) = idle.iter().map(|actor| actor.idle).min() else {
+                continue;
+            };
+            // An actor that is not waiting on a signal is still busy with a step of its work
+            if least < window || idle.iter().any(|actor| actor.waiting_on.is_none()) {
+                reported = false;
+                continue;
+            }
+            if reported {
+                continue;
+            }
+            stalled = true;
+            reported = true;
+            context.log(EventKind::SimulationStalled { idle_seconds: least.as_secs_f64() });
+            for actor in idle {
+                context.log(EventKind::ActorStalled {
+                    stalled: actor.thread,
+                    signal: actor.waiting_on,
+                    idle_seconds: actor.idle.as_secs_f64()
+                });
+            }
+            if abort {
+                context.shutdown.trigger();
+            }
+        }
+        context.clock.deregister();
+        stalled
+    }
+
+    /// Waits for the watchdog to finish, once the simulation has shut down.
+    ///
+    /// # Returns
+    /// True if the simulation stalled, false if it never did or the watchdog panicked.
+    pub fn join(self) -> bool {
+        self.thread.join().unwrap_or(false)
+    }
+}
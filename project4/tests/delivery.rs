@@ -0,0 +1,56 @@
+//! # Hanging on by a Thread: tests/delivery.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Runs a pipelined steward that keeps several deliveries on the way at once, for strongholds
+//! whose recipes share resources, and checks that every delivery reaches the stronghold it was
+//! collected for instead of completing some other stronghold's recipe.
+//!
+//! ## Dependencies
+//! - `project4::scenario::Scenario` for loading the scenario under `tests/scenarios`.
+//! - `project4::simulation` for building, running, and stopping the simulation.
+//! - `serde_json` for reading the statistics of the report.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::{path::Path, time::Duration};
+use serde_json::Value;
+
+use project4::{scenario::Scenario, simulation::SimulationBuilder, steward::StewardMode};
+
+#[test]
+fn overlapping_recipes_are_delivered_to_their_own_stronghold() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios/overlapping.toml");
+    let mut simulation = SimulationBuilder::new(Scenario::load(&path).unwrap())
+        .seed(5)
+        .virtual_time(true)
+        .steward_mode(StewardMode::Pipelined)
+        .build()
+        .unwrap();
+    simulation.start().unwrap();
+    simulation.run_for(Duration::from_secs(86400)).unwrap();
+    let report = simulation.stop();
+
+    // A unit that completed the wrong recipe used to strand the rest of its delivery in the depot,
+    // until it filled up and nothing was delivered for the rest of the day
+    let fairness = report.stats.fairness(report.elapsed);
+    for (name, starvation) in &fairness.strongholds {
+        assert!(starvation.served > 1000, "{} was served {} times", name, starvation.served);
+        assert!(starvation.longest_unserved_seconds < 3600.0,
+                "{} went {} seconds unserved", name, starvation.longest_unserved_seconds);
+    }
+
+    let stats:Value = serde_json::from_str(&report.stats.to_json().unwrap()).unwrap();
+    let delivered = stats["stewards"]["steward"]["delivered"].as_u64().unwrap();
+    let served:u64 = fairness.strongholds.values().map(|starvation| starvation.served).sum();
+    // First and Second each need two units and Third three, but every two of them share one of X,
+    // Y, and Z, whose four units of room bound how many of their deliveries are on the way. Adding
+    // those three bounds counts every delivery twice, so at most 12 / 2 = 6 are on the way, and
+    // the steward counts one more as soon as it is collected, before there is room to place it
+    assert!(delivered - served <= 7, "{} delivered, {} served", delivered, served);
+}
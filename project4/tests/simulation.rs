@@ -0,0 +1,81 @@
+//! # Hanging on by a Thread: tests/simulation.rs
+//! # Version: May 3rd 2024
+//!
+//! ## Description
+//! Runs the classic scenario through the library on a virtual clock, and checks what the report of
+//! the run says, and that a seed replays the run exactly.
+//!
+//! ## Dependencies
+//! - `project4::simulation` for building, running, and stopping the simulation.
+//! - `serde_json` for reading the statistics of the report.
+//!
+//! ## Authors
+//! - Dylan Miller
+//! - Thomas Kay
+//!
+//! ## Instructor
+//! - Dr. William Kreahling
+
+use std::time::Duration;
+use serde_json::Value;
+
+use project4::{scenario::Scenario, simulation::{Report, SimulationBuilder}};
+
+/// Runs the classic scenario on a virtual clock for ten simulated minutes.
+///
+/// # Arguments
+/// * `seed`: The seed of every random number generator.
+///
+/// # Returns
+/// The report of the run.
+fn run_classic(seed:u64) -> Report {
+    let mut simulation = SimulationBuilder::new(Scenario::classic())
+        .seed(seed)
+        .virtual_time(true)
+        .build()
+        .unwrap();
+    simulation.start().unwrap();
+    simulation.run_for(Duration::from_secs(600)).unwrap();
+    simulation.stop()
+}
+
+/// Adds up a count of every entry of a table in the statistics.
+///
+/// # Arguments
+/// * `table`: The table, such as the strongholds.
+/// * `count`: The name of the count, such as `served`.
+fn total(table:&Value, count:&str) -> u64 {
+    table.as_object().unwrap().values().map(|entry| entry[count].as_u64().unwrap()).sum()
+}
+
+#[test]
+fn report_counts_what_the_run_did() {
+    let report = run_classic(42);
+    assert_eq!(report.elapsed, Duration::from_secs(600));
+    assert!(!report.stalled);
+    assert_eq!(report.panicked, 0);
+    assert!(report.log_complete);
+
+    let stats:Value = serde_json::from_str(&report.stats.to_json().unwrap()).unwrap();
+    let delivered = total(&stats["stewards"], "delivered");
+    let served = total(&stats["strongholds"], "served");
+    assert!(delivered > 0);
+    // A delivery is served once both of its resources have been flown to the stronghold
+    assert!(served <= delivered && delivered <= served + 1);
+    let deliveries:u64 = stats["deliveries"].as_object().unwrap().values()
+        .map(|count| count.as_u64().unwrap())
+        .sum();
+    assert_eq!(deliveries, delivered);
+
+    let fairness = report.stats.fairness(report.elapsed);
+    assert_eq!(fairness.strongholds.len(), 3);
+    let index = fairness.index.unwrap();
+    assert!(index > 0.0 && index <= 1.0);
+}
+
+#[test]
+fn same_seed_replays_the_run() {
+    let first = run_classic(7).stats.to_json().unwrap();
+    let second = run_classic(7).stats.to_json().unwrap();
+    assert_eq!(first, second);
+}